@@ -0,0 +1,452 @@
+// Sound effects and music. Every sound is synthesized in code when the game
+// starts, so there are no audio files to ship. Without an audio device bevy
+// logs a warning and plays nothing, and sounds are still cleaned up.
+use std::{f32::consts::TAU, sync::Arc, time::Duration};
+
+use bevy::{
+    audio::{AddAudioSource, AudioPlugin, Decodable, Source, Volume},
+    prelude::*,
+};
+use rand::Rng;
+
+use crate::{
+    region_game::{BallBounced, BrickCaptured},
+    region_sim::Surface,
+    GameState,
+};
+
+const SAMPLE_RATE: u32 = 22_050;
+/// The steps volume options cycle through.
+pub const VOLUME_STEPS: [f32; 5] = [0., 0.25, 0.5, 0.75, 1.];
+// captures closer together than this count as one streak and climb in pitch
+const STREAK_SECONDS: f32 = 0.3;
+const STREAK_PITCH: f32 = 0.04;
+const MAX_STREAK: u32 = 8;
+// keeps a fast match from turning captures into a buzz
+const MIN_CAPTURE_GAP: f32 = 0.05;
+const PITCH_JITTER: f32 = 0.04;
+
+pub struct GameAudioPlugin;
+
+impl Plugin for GameAudioPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<AudioVolumes>().add_event::<PlaySfx>();
+        if !app.is_plugin_added::<AudioPlugin>() {
+            info!("audio is not available, the game runs silently");
+            return;
+        }
+        app.add_audio_source::<Synth>()
+            .add_systems(Startup, setup_sounds)
+            .add_systems(
+                Update,
+                (
+                    play_music.run_if(state_changed::<GameState>),
+                    region_sounds.run_if(in_state(GameState::RegionGame)),
+                    play_sfx,
+                    update_music_volume,
+                    expire_sounds,
+                )
+                    .chain()
+                    .run_if(resource_exists::<SoundBank>),
+            );
+    }
+}
+
+/// Volume of each channel, in `0..=1`. Music and effects are scaled by the
+/// master volume.
+#[derive(Resource, Debug, Clone, Copy)]
+pub struct AudioVolumes {
+    pub master: f32,
+    pub music: f32,
+    pub sfx: f32,
+}
+
+impl Default for AudioVolumes {
+    fn default() -> Self {
+        Self {
+            master: 1.,
+            music: 0.5,
+            sfx: 0.75,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Sfx {
+    Hover,
+    Press,
+    Capture,
+    WallBounce,
+    BallClash,
+}
+
+/// Plays a sound effect. The same effect asked for several times in one frame
+/// plays once.
+#[derive(Event, Debug, Clone, Copy)]
+pub struct PlaySfx(pub Sfx);
+
+/// Mono samples at `SAMPLE_RATE`, synthesized rather than loaded.
+#[derive(Asset, TypePath, Clone)]
+pub struct Synth(Arc<[f32]>);
+
+impl Synth {
+    fn duration(&self) -> Duration {
+        Duration::from_secs_f32(self.0.len() as f32 / SAMPLE_RATE as f32)
+    }
+}
+
+impl Decodable for Synth {
+    type DecoderItem = f32;
+    type Decoder = SynthDecoder;
+
+    fn decoder(&self) -> SynthDecoder {
+        SynthDecoder {
+            samples: self.0.clone(),
+            next: 0,
+        }
+    }
+}
+
+pub struct SynthDecoder {
+    samples: Arc<[f32]>,
+    next: usize,
+}
+
+impl Iterator for SynthDecoder {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<f32> {
+        let sample = self.samples.get(self.next).copied();
+        self.next += 1;
+        sample
+    }
+}
+
+impl Source for SynthDecoder {
+    fn current_frame_len(&self) -> Option<usize> {
+        Some(self.samples.len().saturating_sub(self.next))
+    }
+
+    fn channels(&self) -> u16 {
+        1
+    }
+
+    fn sample_rate(&self) -> u32 {
+        SAMPLE_RATE
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        Some(Duration::from_secs_f32(
+            self.samples.len() as f32 / SAMPLE_RATE as f32,
+        ))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Music {
+    Menu,
+    Region,
+    Rps,
+}
+
+// `None` keeps whatever is playing
+fn state_music(state: GameState) -> Option<Music> {
+    match state {
+        GameState::Menu | GameState::RegionSetup | GameState::Options => Some(Music::Menu),
+        GameState::RegionGame => Some(Music::Region),
+        GameState::RpsGame => Some(Music::Rps),
+        GameState::Restarting => None,
+    }
+}
+
+#[derive(Resource)]
+struct SoundBank {
+    hover: Handle<Synth>,
+    press: Handle<Synth>,
+    capture: Handle<Synth>,
+    wall_bounce: Handle<Synth>,
+    ball_clash: Handle<Synth>,
+    menu_music: Handle<Synth>,
+    region_music: Handle<Synth>,
+    rps_music: Handle<Synth>,
+}
+
+impl SoundBank {
+    fn sfx(&self, sfx: Sfx) -> &Handle<Synth> {
+        match sfx {
+            Sfx::Hover => &self.hover,
+            Sfx::Press => &self.press,
+            Sfx::Capture => &self.capture,
+            Sfx::WallBounce => &self.wall_bounce,
+            Sfx::BallClash => &self.ball_clash,
+        }
+    }
+
+    fn music(&self, music: Music) -> &Handle<Synth> {
+        match music {
+            Music::Menu => &self.menu_music,
+            Music::Region => &self.region_music,
+            Music::Rps => &self.rps_music,
+        }
+    }
+}
+
+#[derive(Component)]
+struct MusicTrack(Music);
+
+// Sound effects are despawned when this runs out, whether or not they could
+// be played
+#[derive(Component)]
+struct SfxLifetime(Timer);
+
+#[derive(Clone, Copy)]
+enum Wave {
+    Sine,
+    Triangle,
+    Square,
+}
+
+impl Wave {
+    // `phase` in turns
+    fn sample(self, phase: f32) -> f32 {
+        let phase = phase.fract();
+        match self {
+            Wave::Sine => (phase * TAU).sin(),
+            Wave::Triangle => 1. - 4. * (phase - 0.5).abs(),
+            Wave::Square => {
+                if phase < 0.5 {
+                    0.5
+                } else {
+                    -0.5
+                }
+            }
+        }
+    }
+}
+
+// A note gliding from `from` to `to` Hz, with a short attack and an exponential
+// decay
+fn chirp(from: f32, to: f32, seconds: f32, wave: Wave, volume: f32) -> Vec<f32> {
+    let mut samples = vec![0.; (seconds * SAMPLE_RATE as f32) as usize];
+    add_note(&mut samples, 0, from, to, seconds, wave, volume);
+    samples
+}
+
+fn add_note(
+    samples: &mut [f32],
+    start: usize,
+    from: f32,
+    to: f32,
+    seconds: f32,
+    wave: Wave,
+    volume: f32,
+) {
+    let length = (seconds * SAMPLE_RATE as f32) as usize;
+    let attack = (0.005 * SAMPLE_RATE as f32) as usize;
+    let mut phase = 0.;
+    for (index, sample) in samples[start..].iter_mut().take(length).enumerate() {
+        let progress = index as f32 / length as f32;
+        phase += (from + (to - from) * progress) / SAMPLE_RATE as f32;
+        let envelope = (index as f32 / attack as f32).min(1.) * (-5. * progress).exp();
+        *sample += wave.sample(phase) * envelope * volume;
+    }
+}
+
+fn midi_hz(note: u8) -> f32 {
+    440. * 2f32.powf((note as f32 - 69.) / 12.)
+}
+
+// A loop of one melody note per beat over a bass note per bar of four beats.
+// The last note rings into the start of the loop, so a loop sounds seamless.
+fn track(melody: &[u8], bass: &[u8], beat: f32, wave: Wave) -> Vec<f32> {
+    let beat_samples = (beat * SAMPLE_RATE as f32) as usize;
+    let mut samples = vec![0.; melody.len() * beat_samples];
+    let length = samples.len();
+    let mut ring = vec![0.; length];
+    for (index, &note) in melody.iter().enumerate() {
+        let hz = midi_hz(note);
+        let mut note_samples = vec![0.; beat_samples * 2];
+        add_note(&mut note_samples, 0, hz, hz, beat * 2., wave, 0.18);
+        for (offset, sample) in note_samples.into_iter().enumerate() {
+            let at = index * beat_samples + offset;
+            if at < length {
+                samples[at] += sample;
+            } else {
+                ring[at - length] += sample;
+            }
+        }
+    }
+    for (index, &note) in bass.iter().enumerate() {
+        let hz = midi_hz(note);
+        add_note(
+            &mut samples,
+            index * beat_samples * 4,
+            hz,
+            hz,
+            beat * 4.,
+            Wave::Sine,
+            0.25,
+        );
+    }
+    for (sample, ring) in samples.iter_mut().zip(ring) {
+        *sample += ring;
+    }
+    samples
+}
+
+fn setup_sounds(mut commands: Commands, mut synths: ResMut<Assets<Synth>>) {
+    let mut add = |samples: Vec<f32>| synths.add(Synth(samples.into()));
+    commands.insert_resource(SoundBank {
+        hover: add(chirp(1400., 1500., 0.03, Wave::Sine, 0.2)),
+        press: add(chirp(700., 350., 0.08, Wave::Square, 0.35)),
+        capture: add(chirp(880., 1320., 0.09, Wave::Triangle, 0.35)),
+        wall_bounce: add(chirp(240., 160., 0.06, Wave::Sine, 0.5)),
+        ball_clash: add(chirp(520., 480., 0.05, Wave::Square, 0.3)),
+        menu_music: add(track(
+            &[
+                60, 64, 67, 72, 57, 60, 64, 69, 53, 57, 60, 65, 55, 59, 62, 67, //
+                72, 67, 64, 60, 69, 64, 60, 57, 65, 60, 57, 53, 67, 62, 59, 55,
+            ],
+            &[48, 45, 41, 43, 48, 45, 41, 43],
+            0.3,
+            Wave::Sine,
+        )),
+        region_music: add(track(
+            &[
+                57, 60, 64, 60, 57, 60, 67, 64, 55, 59, 62, 59, 55, 59, 67, 62, //
+                53, 57, 60, 57, 53, 57, 65, 60, 52, 56, 59, 56, 52, 56, 64, 59,
+            ],
+            &[45, 43, 41, 40, 45, 43, 41, 40],
+            0.16,
+            Wave::Triangle,
+        )),
+        rps_music: add(track(
+            &[
+                67, 71, 74, 71, 67, 72, 76, 72, 69, 72, 76, 72, 67, 71, 74, 79,
+            ],
+            &[43, 48, 45, 43],
+            0.22,
+            Wave::Triangle,
+        )),
+    });
+}
+
+// Keeps the music going when the next state uses the same track
+fn play_music(
+    mut commands: Commands,
+    bank: Res<SoundBank>,
+    volumes: Res<AudioVolumes>,
+    state: Res<State<GameState>>,
+    playing: Query<(Entity, &MusicTrack)>,
+) {
+    let Some(music) = state_music(*state.get()) else {
+        return;
+    };
+    let mut already_playing = false;
+    for (entity, track) in &playing {
+        if track.0 == music {
+            already_playing = true;
+        } else {
+            commands.entity(entity).despawn();
+        }
+    }
+    if !already_playing {
+        commands.spawn((
+            AudioPlayer(bank.music(music).clone()),
+            PlaybackSettings::LOOP.with_volume(Volume::new(volumes.master * volumes.music)),
+            MusicTrack(music),
+        ));
+    }
+}
+
+// Captures and bounces on the Region Battle board
+fn region_sounds(
+    mut captures: EventReader<BrickCaptured>,
+    mut bounces: EventReader<BallBounced>,
+    mut sfx: EventWriter<PlaySfx>,
+) {
+    if captures.read().count() > 0 {
+        sfx.send(PlaySfx(Sfx::Capture));
+    }
+    for bounce in bounces.read() {
+        match bounce.surface {
+            Surface::Wall(_) => {
+                sfx.send(PlaySfx(Sfx::WallBounce));
+            }
+            Surface::Ball(_) => {
+                sfx.send(PlaySfx(Sfx::BallClash));
+            }
+            Surface::Brick(_) => {}
+        }
+    }
+}
+
+fn play_sfx(
+    mut commands: Commands,
+    mut events: EventReader<PlaySfx>,
+    bank: Res<SoundBank>,
+    synths: Res<Assets<Synth>>,
+    volumes: Res<AudioVolumes>,
+    time: Res<Time<Real>>,
+    // number of captures in the current streak and when the last one played
+    mut streak: Local<(u32, f32)>,
+) {
+    let mut played = Vec::new();
+    for PlaySfx(sfx) in events.read() {
+        if played.contains(sfx) {
+            continue;
+        }
+        played.push(*sfx);
+        let mut pitch = 1. + rand::thread_rng().gen_range(-PITCH_JITTER..=PITCH_JITTER);
+        if *sfx == Sfx::Capture {
+            let now = time.elapsed_secs();
+            let since = now - streak.1;
+            if since < MIN_CAPTURE_GAP {
+                continue;
+            }
+            streak.0 = if since < STREAK_SECONDS {
+                (streak.0 + 1).min(MAX_STREAK)
+            } else {
+                0
+            };
+            streak.1 = now;
+            pitch += streak.0 as f32 * STREAK_PITCH;
+        }
+
+        let handle = bank.sfx(*sfx);
+        let length = synths
+            .get(handle)
+            .map_or(Duration::ZERO, |synth| synth.duration());
+        commands.spawn((
+            AudioPlayer(handle.clone()),
+            PlaybackSettings::DESPAWN
+                .with_volume(Volume::new(volumes.master * volumes.sfx))
+                .with_speed(pitch),
+            SfxLifetime(Timer::new(
+                length + Duration::from_millis(500),
+                TimerMode::Once,
+            )),
+        ));
+    }
+}
+
+fn update_music_volume(volumes: Res<AudioVolumes>, sinks: Query<&AudioSink, With<MusicTrack>>) {
+    if !volumes.is_changed() {
+        return;
+    }
+    for sink in &sinks {
+        sink.set_volume(volumes.master * volumes.music);
+    }
+}
+
+fn expire_sounds(
+    mut commands: Commands,
+    time: Res<Time<Real>>,
+    mut sounds: Query<(Entity, &mut SfxLifetime)>,
+) {
+    for (entity, mut lifetime) in &mut sounds {
+        if lifetime.0.tick(time.delta()).finished() {
+            commands.entity(entity).despawn();
+        }
+    }
+}
@@ -7,5 +7,6 @@ pub const PRESSED_BUTTON: Color = Color::srgb(0.35, 0.75, 0.35);
 pub const BACKGROUND: Color = Color::srgb(0.27, 0.43, 0.8);
 
 pub const TEXT_COLOR: Color = Color::srgb(0.9, 0.9, 0.9);
+pub const GAME_DATA_TEXT_COLOR: Color = Color::srgb(0., 0.22, 0.76);
 
 pub const FIRASANS_FONT: &str = "fonts/FiraSans-Bold.ttf";
@@ -0,0 +1,587 @@
+// Batch runner for Region Battle matches without a window or GPU. It steps
+// `RegionWorld` directly at the fixed tick rate, so thousands of matches finish in
+// seconds. Used to balance settings and to catch physics regressions on CI:
+//
+//     gametrain --headless --size 31 --seeds 0..1000 --speed 100 --ticks 7680
+//
+// `--bench` instead times a single long match and fails when a tick takes longer
+// than the fixed timestep, e.g. `--bench --size 200 --balls 25 --ticks 6400`.
+use std::{fmt::Write, ops::Range, time::Instant};
+
+use crate::region_sim::{
+    Layout, MatchOutcome, MatchSettings, PowerUpKind, RegionMap, RegionWorld, SpeedCurve,
+    StalemateResponse, TeamId, BALL_SPEED, BRICK_COUNT_WIDTH, TICK_SECONDS,
+};
+
+const USAGE: &str = "usage: gametrain --headless [options]
+
+options:
+    --size <bricks>       bricks along each side of the board (default 31)
+    --seeds <from..to>    seeds to play, one match per seed (default 0..100)
+    --speed <speed>       launch speed of both balls (default 100)
+    --red-speed <speed>   launch speed of the red ball
+    --blue-speed <speed>  launch speed of the blue ball
+    --balls <count>       balls per team (default 1)
+    --layout <layout>     starting layout: vertical, horizontal, diagonal,
+                          checkerboard, rings, blobs or fair-random
+                          (default vertical)
+    --map <file>          play a map file instead of a generated board, see
+                          `assets/maps/` for the format
+    --power-ups <kinds>   comma separated power-ups to enable: speed, growth,
+                          multi-ball, shield, paint-bomb, or all (default none)
+    --ball-collisions     balls of different teams bounce off each other
+                          instead of passing through
+    --restitution <0..1>  bounciness of ball-ball collisions (default 1)
+    --contact-steals      the harder hitting ball captures the cell where two
+                          balls collide
+    --stalemate <response>
+                          what to do when a match loops: jitter, speed-up,
+                          draw or off (default off), logged to stderr
+    --speed-per-capture <percent>
+                          speed a team up by this much per captured brick
+    --speed-per-second <percent>
+                          speed every ball up by this much per second
+    --max-speed <multiple>
+                          cap for the speed-ups, as a multiple of the launch
+                          speed (default 1)
+    --sudden-death <seconds>
+                          speed every ball up past the cap for the last
+                          seconds before the tick limit
+    --ticks <ticks>       tick limit per match, 64 ticks per second (default 7680)
+    --format <csv|json>   output format (default csv)
+    --bench               time one match with the first seed and check that it
+                          keeps up with the fixed tick rate";
+
+// two minutes at the default fixed rate
+const DEFAULT_TICK_LIMIT: u32 = 64 * 120;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Format {
+    Csv,
+    Json,
+}
+
+#[derive(Debug, Clone)]
+struct Options {
+    size: usize,
+    seeds: Range<u64>,
+    speeds: [f32; 2],
+    balls_per_team: usize,
+    layout: Layout,
+    map: Option<RegionMap>,
+    power_ups: Vec<PowerUpKind>,
+    ball_collisions: bool,
+    restitution: f32,
+    contact_steals: bool,
+    stalemate: Option<StalemateResponse>,
+    speed_curve: SpeedCurve,
+    tick_limit: u32,
+    format: Format,
+    bench: bool,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Self {
+            size: BRICK_COUNT_WIDTH,
+            seeds: 0..100,
+            speeds: [BALL_SPEED; 2],
+            balls_per_team: 1,
+            layout: Layout::default(),
+            map: None,
+            power_ups: Vec::new(),
+            ball_collisions: false,
+            restitution: 1.,
+            contact_steals: false,
+            stalemate: None,
+            speed_curve: SpeedCurve::default(),
+            tick_limit: DEFAULT_TICK_LIMIT,
+            format: Format::Csv,
+            bench: false,
+        }
+    }
+}
+
+struct MatchReport {
+    seed: u64,
+    ticks: u32,
+    red: usize,
+    blue: usize,
+    stalemates: usize,
+    outcome: MatchOutcome,
+}
+
+#[derive(Default)]
+struct Summary {
+    matches: usize,
+    red_wins: usize,
+    blue_wins: usize,
+    draws: usize,
+}
+
+impl Summary {
+    fn new(reports: &[MatchReport]) -> Self {
+        let mut summary = Summary {
+            matches: reports.len(),
+            ..Default::default()
+        };
+        for report in reports {
+            match report.outcome {
+                MatchOutcome::Winner(TeamId::RED) => summary.red_wins += 1,
+                MatchOutcome::Winner(_) => summary.blue_wins += 1,
+                MatchOutcome::Draw => summary.draws += 1,
+            }
+        }
+        summary
+    }
+
+    fn rate(&self, count: usize) -> f64 {
+        if self.matches == 0 {
+            0.
+        } else {
+            count as f64 / self.matches as f64
+        }
+    }
+}
+
+/// Entry point for `--headless`. Returns the process exit code.
+pub fn run(args: impl Iterator<Item = String>) -> i32 {
+    let args: Vec<String> = args.collect();
+    if args.iter().any(|arg| arg == "--help" || arg == "-h") {
+        println!("{USAGE}");
+        return 0;
+    }
+    let options = match parse_args(args.into_iter()) {
+        Ok(options) => options,
+        Err(message) => {
+            eprintln!("error: {message}\n\n{USAGE}");
+            return 2;
+        }
+    };
+    if options.bench {
+        return bench(&options);
+    }
+
+    let reports: Vec<MatchReport> = options
+        .seeds
+        .clone()
+        .map(|seed| play(&options, seed))
+        .collect();
+    let output = match options.format {
+        Format::Csv => to_csv(&options, &reports),
+        Format::Json => to_json(&options, &reports),
+    };
+    print!("{output}");
+    0
+}
+
+fn parse_args(mut args: impl Iterator<Item = String>) -> Result<Options, String> {
+    let mut options = Options::default();
+    let mut red_speed = None;
+    let mut blue_speed = None;
+    while let Some(arg) = args.next() {
+        let mut value = || {
+            args.next()
+                .ok_or_else(|| format!("missing value for `{arg}`"))
+        };
+        match arg.as_str() {
+            "--size" => options.size = parse_number(&value()?)?,
+            "--seeds" => options.seeds = parse_range(&value()?)?,
+            "--speed" => options.speeds = [parse_number(&value()?)?; 2],
+            "--red-speed" => red_speed = Some(parse_number(&value()?)?),
+            "--blue-speed" => blue_speed = Some(parse_number(&value()?)?),
+            "--balls" => options.balls_per_team = parse_number(&value()?)?,
+            "--layout" => {
+                let name = value()?;
+                options.layout =
+                    Layout::from_name(&name).ok_or_else(|| format!("unknown layout `{name}`"))?;
+            }
+            "--map" => options.map = Some(read_map(&value()?)?),
+            "--power-ups" => options.power_ups = parse_power_ups(&value()?)?,
+            "--restitution" => options.restitution = parse_number(&value()?)?,
+            "--ball-collisions" => options.ball_collisions = true,
+            "--contact-steals" => options.contact_steals = true,
+            "--stalemate" => {
+                let name = value()?;
+                options.stalemate = match name.as_str() {
+                    "off" => None,
+                    _ => Some(
+                        StalemateResponse::from_name(&name)
+                            .ok_or_else(|| format!("unknown stalemate response `{name}`"))?,
+                    ),
+                }
+            }
+            "--speed-per-capture" => {
+                options.speed_curve.per_capture = parse_number::<f32>(&value()?)? / 100.
+            }
+            "--speed-per-second" => {
+                options.speed_curve.per_second = parse_number::<f32>(&value()?)? / 100.
+            }
+            "--max-speed" => options.speed_curve.max_speed = parse_number(&value()?)?,
+            "--sudden-death" => {
+                let seconds: f32 = parse_number(&value()?)?;
+                options.speed_curve.sudden_death = Some((seconds / TICK_SECONDS).round() as u32);
+            }
+            "--bench" => options.bench = true,
+            "--ticks" => options.tick_limit = parse_number(&value()?)?,
+            "--format" => {
+                options.format = match value()?.as_str() {
+                    "csv" => Format::Csv,
+                    "json" => Format::Json,
+                    other => return Err(format!("unknown format `{other}`")),
+                }
+            }
+            other => return Err(format!("unknown option `{other}`")),
+        }
+    }
+    options.speeds = [
+        red_speed.unwrap_or(options.speeds[0]),
+        blue_speed.unwrap_or(options.speeds[1]),
+    ];
+    // the balls start a quarter of the way in, which needs at least a few bricks
+    if options.size < 4 {
+        return Err("`--size` must be at least 4".to_string());
+    }
+    if !(0.0..=1.0).contains(&options.restitution) {
+        return Err("`--restitution` must be between 0 and 1".to_string());
+    }
+    if options.speed_curve.max_speed < 1. {
+        return Err("`--max-speed` must be at least 1".to_string());
+    }
+    Ok(options)
+}
+
+fn read_map(path: &str) -> Result<RegionMap, String> {
+    let source = std::fs::read_to_string(path)
+        .map_err(|error| format!("could not read `{path}`: {error}"))?;
+    let fallback_name = std::path::Path::new(path)
+        .file_stem()
+        .and_then(|stem| stem.to_str())
+        .unwrap_or(path);
+    RegionMap::parse(&source, fallback_name).map_err(|error| format!("{path}: {error}"))
+}
+
+fn parse_power_ups(value: &str) -> Result<Vec<PowerUpKind>, String> {
+    if value == "all" {
+        return Ok(PowerUpKind::ALL.to_vec());
+    }
+    value
+        .split(',')
+        .map(|name| {
+            PowerUpKind::from_name(name).ok_or_else(|| format!("unknown power-up `{name}`"))
+        })
+        .collect()
+}
+
+fn parse_number<T: std::str::FromStr>(value: &str) -> Result<T, String> {
+    value
+        .parse()
+        .map_err(|_| format!("`{value}` is not a valid number"))
+}
+
+// Accepts `from..to` or a single seed
+fn parse_range(value: &str) -> Result<Range<u64>, String> {
+    match value.split_once("..") {
+        Some((from, to)) => Ok(parse_number(from)?..parse_number(to)?),
+        None => {
+            let seed = parse_number(value)?;
+            Ok(seed..seed + 1)
+        }
+    }
+}
+
+fn new_world(options: &Options, seed: u64) -> RegionWorld {
+    RegionWorld::new(&MatchSettings {
+        board_size: options.size,
+        ball_speeds: options.speeds,
+        balls_per_team: options.balls_per_team,
+        layout: options.layout,
+        seed: Some(seed),
+        map: options.map.clone(),
+        power_ups: options.power_ups.clone(),
+        steering: false,
+        boost: false,
+        barriers: false,
+        ball_collisions: options.ball_collisions,
+        restitution: options.restitution,
+        contact_steals: options.contact_steals,
+        stalemate: options.stalemate,
+        speed_curve: options.speed_curve,
+        tick_limit: Some(options.tick_limit),
+    })
+}
+
+fn play(options: &Options, seed: u64) -> MatchReport {
+    let mut world = new_world(options, seed);
+    let mut stalemates = 0;
+    let outcome = loop {
+        if let Some(outcome) = world.outcome() {
+            break outcome;
+        }
+        world.step(TICK_SECONDS);
+        world.clear_changes();
+        for stalemate in world.drain_stalemates() {
+            stalemates += 1;
+            eprintln!("seed {seed}: {stalemate}");
+        }
+    };
+    MatchReport {
+        seed,
+        ticks: world.tick(),
+        red: world.count(TeamId::RED),
+        blue: world.count(TeamId::BLUE),
+        stalemates,
+        outcome,
+    }
+}
+
+// Steps the full tick limit (a match ending early is not interesting here) and
+// compares the time per tick against the fixed timestep.
+fn bench(options: &Options) -> i32 {
+    let mut world = new_world(options, options.seeds.start);
+    let start = Instant::now();
+    for _ in 0..options.tick_limit {
+        world.step(TICK_SECONDS);
+        world.clear_changes();
+        world.drain_stalemates();
+    }
+    let elapsed = start.elapsed().as_secs_f64();
+    let per_tick = elapsed / options.tick_limit.max(1) as f64;
+    let holds_rate = per_tick <= TICK_SECONDS as f64;
+    println!(
+        "size {0}x{0}, {1} balls, {2} ticks in {3:.3}s: {4:.1} ticks/s, {5:.3} ms/tick ({6} {7:.3} ms budget)",
+        options.size,
+        world.balls().len(),
+        options.tick_limit,
+        elapsed,
+        options.tick_limit as f64 / elapsed,
+        per_tick * 1000.,
+        if holds_rate { "within" } else { "OVER" },
+        TICK_SECONDS * 1000.,
+    );
+    if holds_rate {
+        0
+    } else {
+        1
+    }
+}
+
+// What the match was played on, the map name or the layout
+fn arena_name(options: &Options) -> &str {
+    match &options.map {
+        Some(map) => &map.name,
+        None => options.layout.name(),
+    }
+}
+
+// Bricks along each side, the map's own dimensions when one was played
+fn board_size(options: &Options) -> String {
+    match &options.map {
+        Some(map) => format!("{}x{}", map.columns, map.rows),
+        None => options.size.to_string(),
+    }
+}
+
+// Quoted when it holds a separator, a quote or a line break
+fn csv_field(text: &str) -> String {
+    if text.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", text.replace('"', "\"\""))
+    } else {
+        text.to_string()
+    }
+}
+
+// A JSON string, quotes included
+fn json_string(text: &str) -> String {
+    let mut out = String::from("\"");
+    for c in text.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if c.is_control() => {
+                let _ = write!(out, "\\u{:04x}", c as u32);
+            }
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+fn winner_name(outcome: MatchOutcome) -> &'static str {
+    match outcome {
+        MatchOutcome::Winner(TeamId::RED) => "red",
+        MatchOutcome::Winner(_) => "blue",
+        MatchOutcome::Draw => "draw",
+    }
+}
+
+fn to_csv(options: &Options, reports: &[MatchReport]) -> String {
+    let [red_speed, blue_speed] = options.speeds;
+    let mut out = String::from(
+        "seed,size,arena,red_speed,blue_speed,ticks,red_cells,blue_cells,stalemates,winner\n",
+    );
+    for report in reports {
+        let _ = writeln!(
+            out,
+            "{},{},{},{},{},{},{},{},{},{}",
+            report.seed,
+            board_size(options),
+            csv_field(arena_name(options)),
+            red_speed,
+            blue_speed,
+            report.ticks,
+            report.red,
+            report.blue,
+            report.stalemates,
+            winner_name(report.outcome),
+        );
+    }
+
+    let summary = Summary::new(reports);
+    out.push_str("\nmatches,red_wins,blue_wins,draws,red_win_rate,blue_win_rate,draw_rate\n");
+    let _ = writeln!(
+        out,
+        "{},{},{},{},{:.4},{:.4},{:.4}",
+        summary.matches,
+        summary.red_wins,
+        summary.blue_wins,
+        summary.draws,
+        summary.rate(summary.red_wins),
+        summary.rate(summary.blue_wins),
+        summary.rate(summary.draws),
+    );
+    out
+}
+
+fn to_json(options: &Options, reports: &[MatchReport]) -> String {
+    let [red_speed, blue_speed] = options.speeds;
+    let mut out = String::from("{\n");
+    let _ = writeln!(
+        out,
+        "  \"settings\": {{\"size\": {}, \"arena\": {}, \"red_speed\": {}, \"blue_speed\": {}, \"tick_limit\": {}}},",
+        match options.map {
+            Some(_) => json_string(&board_size(options)),
+            None => board_size(options),
+        },
+        json_string(arena_name(options)),
+        red_speed,
+        blue_speed,
+        options.tick_limit,
+    );
+    out.push_str("  \"matches\": [\n");
+    for (index, report) in reports.iter().enumerate() {
+        let _ = write!(
+            out,
+            "    {{\"seed\": {}, \"ticks\": {}, \"red_cells\": {}, \"blue_cells\": {}, \"stalemates\": {}, \"winner\": \"{}\"}}",
+            report.seed,
+            report.ticks,
+            report.red,
+            report.blue,
+            report.stalemates,
+            winner_name(report.outcome),
+        );
+        out.push_str(if index + 1 < reports.len() {
+            ",\n"
+        } else {
+            "\n"
+        });
+    }
+    out.push_str("  ],\n");
+
+    let summary = Summary::new(reports);
+    let _ = writeln!(
+        out,
+        "  \"summary\": {{\"matches\": {}, \"red_wins\": {}, \"blue_wins\": {}, \"draws\": {}, \"red_win_rate\": {:.4}, \"blue_win_rate\": {:.4}, \"draw_rate\": {:.4}}}",
+        summary.matches,
+        summary.red_wins,
+        summary.blue_wins,
+        summary.draws,
+        summary.rate(summary.red_wins),
+        summary.rate(summary.blue_wins),
+        summary.rate(summary.draws),
+    );
+    out.push_str("}\n");
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<Options, String> {
+        parse_args(args.iter().map(|arg| arg.to_string()))
+    }
+
+    fn report(seed: u64) -> MatchReport {
+        MatchReport {
+            seed,
+            ticks: 10,
+            red: 2,
+            blue: 2,
+            stalemates: 0,
+            outcome: MatchOutcome::Draw,
+        }
+    }
+
+    #[test]
+    fn seeds_take_a_range_or_a_single_seed() {
+        assert_eq!(parse(&["--seeds", "3..7"]).unwrap().seeds, 3..7);
+        assert_eq!(parse(&["--seeds", "12"]).unwrap().seeds, 12..13);
+        assert_eq!(parse(&[]).unwrap().seeds, 0..100);
+        assert_eq!(
+            parse(&["--seeds", "3..x"]).unwrap_err(),
+            "`x` is not a valid number"
+        );
+        assert_eq!(
+            parse(&["--seeds"]).unwrap_err(),
+            "missing value for `--seeds`"
+        );
+    }
+
+    #[test]
+    fn unknown_options_are_errors() {
+        assert_eq!(
+            parse(&["--size", "20", "--sedes", "0..4"]).unwrap_err(),
+            "unknown option `--sedes`"
+        );
+        assert_eq!(
+            parse(&["--format", "xml"]).unwrap_err(),
+            "unknown format `xml`"
+        );
+        assert_eq!(
+            parse(&["--layout", "zigzag"]).unwrap_err(),
+            "unknown layout `zigzag`"
+        );
+    }
+
+    #[test]
+    fn arena_names_are_escaped() {
+        assert_eq!(csv_field("plain"), "plain");
+        assert_eq!(csv_field("a,b"), "\"a,b\"");
+        assert_eq!(csv_field("say \"hi\""), "\"say \"\"hi\"\"\"");
+        assert_eq!(csv_field("back\\slash"), "back\\slash");
+        assert_eq!(json_string("a,b"), "\"a,b\"");
+        assert_eq!(json_string("say \"hi\""), "\"say \\\"hi\\\"\"");
+        assert_eq!(json_string("back\\slash"), "\"back\\\\slash\"");
+        assert_eq!(json_string("bell\u{7}"), "\"bell\\u0007\"");
+
+        let map = RegionMap::parse("name: Odd, \"quoted\" \\ arena\n---\n1R\nB2\n", "odd").unwrap();
+        let options = Options {
+            map: Some(map),
+            ..Options::default()
+        };
+        let reports = [report(0), report(1)];
+        let csv = to_csv(&options, &reports);
+        let row = csv.lines().nth(1).unwrap();
+        assert_eq!(
+            row,
+            "0,2x2,\"Odd, \"\"quoted\"\" \\ arena\",100,100,10,2,2,0,draw"
+        );
+        let json = to_json(&options, &reports);
+        assert!(json.contains("\"size\": \"2x2\", \"arena\": \"Odd, \\\"quoted\\\" \\\\ arena\""));
+    }
+}
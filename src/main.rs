@@ -1,6 +1,21 @@
+mod audio;
 mod common;
+mod headless;
 mod menu;
+mod options;
+mod pause;
+mod region_chart;
+mod region_effects;
 mod region_game;
+mod region_heatmap;
+mod region_instant_replay;
+mod region_maps;
+mod region_power_ups;
+mod region_replay;
+mod region_save;
+mod region_setup;
+mod region_sim;
+mod region_speed;
 mod rps_game;
 
 mod utils;
@@ -13,27 +28,53 @@ const WINDOW_HEIGHT: f32 = 1000.;
 enum GameState {
     #[default]
     Menu,
+    RegionSetup,
     RegionGame,
     RpsGame,
+    Options,
+    /// Passed through for a frame when a paused game restarts, so the game's
+    /// `OnExit` and `OnEnter` systems run again.
+    Restarting,
 }
 
 fn main() {
-    App::new()
-        .add_plugins(DefaultPlugins.set(WindowPlugin {
-            primary_window: Some(Window {
-                title: "games collections".to_string(),
-                resizable: false,
-                resolution: WindowResolution::new(WINDOW_WIDTH, WINDOW_HEIGHT),
-                ..default()
-            }),
+    let mut args = std::env::args().skip(1).peekable();
+    if args.peek().is_some_and(|arg| arg == "--headless") {
+        std::process::exit(headless::run(args.skip(1)));
+    }
+
+    let mut app = App::new();
+    // draw the Region Battle board as one texture instead of a sprite per brick
+    if std::env::args().any(|arg| arg == "--board-texture") {
+        app.insert_resource(region_game::BoardBackend::Texture);
+    }
+    app.add_plugins(DefaultPlugins.set(WindowPlugin {
+        primary_window: Some(Window {
+            title: "games collections".to_string(),
+            resizable: false,
+            resolution: WindowResolution::new(WINDOW_WIDTH, WINDOW_HEIGHT),
             ..default()
-        }))
-        .init_state::<GameState>()
-        .add_systems(Startup, camera_setup)
-        .add_plugins(menu::MenuPlugin)
-        .add_plugins(region_game::RegionGamePlugin)
-        .add_plugins(rps_game::RpsGamePlugin)
-        .run();
+        }),
+        ..default()
+    }))
+    .init_state::<GameState>()
+    .add_systems(Startup, camera_setup)
+    .add_plugins(audio::GameAudioPlugin)
+    .add_plugins(menu::MenuPlugin)
+    .add_plugins(options::OptionsPlugin)
+    .add_plugins(region_maps::RegionMapsPlugin)
+    .add_plugins(region_setup::RegionSetupPlugin)
+    .add_plugins(region_game::RegionGamePlugin)
+    .add_plugins(region_chart::RegionChartPlugin)
+    .add_plugins(region_effects::RegionEffectsPlugin)
+    .add_plugins(region_heatmap::RegionHeatmapPlugin)
+    .add_plugins(region_power_ups::RegionPowerUpsPlugin)
+    .add_plugins(region_replay::RegionReplayPlugin)
+    .add_plugins(region_save::RegionSavePlugin)
+    .add_plugins(region_speed::RegionSpeedPlugin)
+    .add_plugins(region_instant_replay::RegionInstantReplayPlugin)
+    .add_plugins(rps_game::RpsGamePlugin)
+    .run();
 }
 
 fn camera_setup(mut commands: Commands) {
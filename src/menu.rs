@@ -26,7 +26,7 @@ impl Plugin for MenuPlugin {
 enum MenuButtonAction {
     RegionBattle,
     RPSBattle,
-    HardPlay,
+    Options,
     Help,
     Quit,
 }
@@ -57,7 +57,7 @@ fn main_menu_setup(mut commands: Commands, asset_server: Res<AssetServer>) {
                         padding: UiRect::px(120., 120., 10., 30.),
                         ..default()
                     },
-                    TextColor(BACKGROUND.into()),
+                    TextColor(BACKGROUND),
                 ))
                 .with_children(|parent| {
                     // Display the game name
@@ -78,7 +78,7 @@ fn main_menu_setup(mut commands: Commands, asset_server: Res<AssetServer>) {
                     // Display three buttons for each action available from the main menu:
                     // - Easy Mode
                     // - Normal Mode
-                    // - Hard Mode
+                    // - Options
                     // - Help
                     // - quit
                     parent.spawn_button(
@@ -94,9 +94,9 @@ fn main_menu_setup(mut commands: Commands, asset_server: Res<AssetServer>) {
                         &asset_server,
                     );
                     parent.spawn_button(
-                        MenuButtonAction::HardPlay,
-                        "right.png",
-                        "Hard",
+                        MenuButtonAction::Options,
+                        "wrench.png",
+                        "Options",
                         &asset_server,
                     );
                     parent.spawn_button(
@@ -126,8 +126,9 @@ fn menu_action(
     for (interaction, menu_button_action) in &interaction_query {
         if *interaction == Interaction::Pressed {
             match menu_button_action {
-                MenuButtonAction::RegionBattle => game_state.set(GameState::RegionGame),
+                MenuButtonAction::RegionBattle => game_state.set(GameState::RegionSetup),
                 MenuButtonAction::RPSBattle => game_state.set(GameState::RpsGame),
+                MenuButtonAction::Options => game_state.set(GameState::Options),
                 _ => {}
             }
         }
@@ -0,0 +1,214 @@
+// Options shared by every game, reached from the main menu and from the pause
+// menu of games without a settings screen of their own.
+use bevy::prelude::*;
+
+use crate::{
+    audio::{AudioVolumes, VOLUME_STEPS},
+    common::*,
+    region_effects::EffectSettings,
+    utils::{common_button_system, despawn_with_component, EntitySpawner},
+    GameState,
+};
+
+pub struct OptionsPlugin;
+
+impl Plugin for OptionsPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(OnEnter(GameState::Options), setup_screen)
+            .add_systems(
+                OnExit(GameState::Options),
+                despawn_with_component::<OnOptionsScreen>,
+            )
+            .add_systems(
+                Update,
+                (
+                    common_button_system,
+                    volume_button,
+                    effect_button,
+                    options_action,
+                )
+                    .run_if(in_state(GameState::Options)),
+            );
+    }
+}
+
+#[derive(Component)]
+struct OnOptionsScreen;
+
+/// Cycles a volume channel through `VOLUME_STEPS`.
+#[derive(Component, Clone, Copy)]
+enum VolumeButton {
+    Master,
+    Music,
+    Sfx,
+}
+
+impl VolumeButton {
+    fn volume(self, volumes: &mut AudioVolumes) -> &mut f32 {
+        match self {
+            VolumeButton::Master => &mut volumes.master,
+            VolumeButton::Music => &mut volumes.music,
+            VolumeButton::Sfx => &mut volumes.sfx,
+        }
+    }
+
+    fn title(self, volume: f32) -> String {
+        let name = match self {
+            VolumeButton::Master => "Master",
+            VolumeButton::Music => "Music",
+            VolumeButton::Sfx => "SFX",
+        };
+        format!("{name} {:.0}%", volume * 100.)
+    }
+}
+
+/// Turns one of the Region Battle effects on or off.
+#[derive(Component, Clone, Copy)]
+enum EffectButton {
+    BrickPop,
+    Particles,
+    BallSquash,
+    Trails,
+    CameraShake,
+}
+
+impl EffectButton {
+    const ALL: [EffectButton; 5] = [
+        EffectButton::BrickPop,
+        EffectButton::Particles,
+        EffectButton::BallSquash,
+        EffectButton::Trails,
+        EffectButton::CameraShake,
+    ];
+
+    fn setting(self, settings: &mut EffectSettings) -> &mut bool {
+        match self {
+            EffectButton::BrickPop => &mut settings.brick_pop,
+            EffectButton::Particles => &mut settings.particles,
+            EffectButton::BallSquash => &mut settings.ball_squash,
+            EffectButton::Trails => &mut settings.trails,
+            EffectButton::CameraShake => &mut settings.camera_shake,
+        }
+    }
+
+    fn title(self, enabled: bool) -> String {
+        let name = match self {
+            EffectButton::BrickPop => "Brick Pop",
+            EffectButton::Particles => "Particles",
+            EffectButton::BallSquash => "Squash",
+            EffectButton::Trails => "Trails",
+            EffectButton::CameraShake => "Shake",
+        };
+        format!("{name} {}", if enabled { "On" } else { "Off" })
+    }
+}
+
+#[derive(Component)]
+struct BackButton;
+
+fn setup_screen(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    volumes: Res<AudioVolumes>,
+    effects: Res<EffectSettings>,
+) {
+    // copies, the accessors below hand out mutable references
+    let (mut volumes, mut effects) = (*volumes, *effects);
+    let column = Node {
+        flex_direction: FlexDirection::Column,
+        align_items: AlignItems::Center,
+        ..default()
+    };
+    commands
+        .spawn((
+            Node {
+                width: Val::Percent(100.0),
+                height: Val::Percent(100.0),
+                flex_direction: FlexDirection::Column,
+                align_items: AlignItems::Center,
+                justify_content: JustifyContent::Center,
+                ..default()
+            },
+            OnOptionsScreen,
+        ))
+        .with_children(|parent| {
+            parent.spawn((
+                Text::new("OPTIONS"),
+                TextFont {
+                    font: asset_server.load(FIRASANS_FONT),
+                    font_size: 80.0,
+                    ..Default::default()
+                },
+                TextColor(TEXT_COLOR),
+                Node {
+                    margin: UiRect::all(Val::Px(50.0)),
+                    ..default()
+                },
+            ));
+            parent.spawn(Node::default()).with_children(|parent| {
+                parent.spawn(column.clone()).with_children(|parent| {
+                    for button in [VolumeButton::Master, VolumeButton::Music, VolumeButton::Sfx] {
+                        let title = button.title(*button.volume(&mut volumes));
+                        parent.spawn_button(button, "wrench.png", &title, &asset_server);
+                    }
+                    parent.spawn_button(BackButton, "exitRight.png", "Back", &asset_server);
+                });
+                parent.spawn(column).with_children(|parent| {
+                    for button in EffectButton::ALL {
+                        let title = button.title(*button.setting(&mut effects));
+                        parent.spawn_button(button, "wrench.png", &title, &asset_server);
+                    }
+                });
+            });
+        });
+}
+
+fn volume_button(
+    interaction_query: Query<(&Interaction, &VolumeButton, &Children), Changed<Interaction>>,
+    mut text_query: Query<&mut Text>,
+    mut volumes: ResMut<AudioVolumes>,
+) {
+    for (interaction, button, children) in &interaction_query {
+        if *interaction != Interaction::Pressed {
+            continue;
+        }
+        let volume = button.volume(&mut volumes);
+        let step = VOLUME_STEPS
+            .iter()
+            .position(|step| *step >= *volume)
+            .unwrap_or(0);
+        *volume = VOLUME_STEPS[(step + 1) % VOLUME_STEPS.len()];
+        // the icon is the first child, the title the second
+        if let Ok(mut text) = text_query.get_mut(children[1]) {
+            **text = button.title(*volume);
+        }
+    }
+}
+
+fn effect_button(
+    interaction_query: Query<(&Interaction, &EffectButton, &Children), Changed<Interaction>>,
+    mut text_query: Query<&mut Text>,
+    mut effects: ResMut<EffectSettings>,
+) {
+    for (interaction, button, children) in &interaction_query {
+        if *interaction != Interaction::Pressed {
+            continue;
+        }
+        let setting = button.setting(&mut effects);
+        *setting = !*setting;
+        if let Ok(mut text) = text_query.get_mut(children[1]) {
+            **text = button.title(*setting);
+        }
+    }
+}
+
+fn options_action(
+    interaction_query: Query<&Interaction, (Changed<Interaction>, With<BackButton>)>,
+    mut game_state: ResMut<NextState<GameState>>,
+) {
+    for interaction in &interaction_query {
+        if *interaction == Interaction::Pressed {
+            game_state.set(GameState::Menu);
+        }
+    }
+}
@@ -0,0 +1,280 @@
+// A pause menu shared by the games. A game opts in with `add_pause_menu`, after
+// which Esc pauses it: virtual time stops, so none of its `FixedUpdate` systems
+// run, and an overlay offers to resume, restart, or quit to the settings or the
+// menu.
+use bevy::{prelude::*, ui::FocusPolicy};
+
+use crate::{
+    common::{BACKGROUND, FIRASANS_FONT, TEXT_COLOR},
+    utils::{common_button_system, despawn_with_component, EntitySpawner},
+    GameState,
+};
+
+const OVERLAY_COLOR: Color = Color::srgba(0., 0., 0., 0.6);
+
+/// Whether the game being played is paused. Shared by every game that opted
+/// in, and set back to `Running` when one of them is left.
+#[derive(States, Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub(crate) enum PauseState {
+    #[default]
+    Running,
+    Paused,
+}
+
+pub trait PauseMenuExt {
+    /// Lets `game` be paused. The Quit to Settings button ends the match for
+    /// `settings` once pressed twice, and is left out without one.
+    fn add_pause_menu(&mut self, game: GameState, settings: Option<GameState>) -> &mut Self;
+}
+
+impl PauseMenuExt for App {
+    fn add_pause_menu(&mut self, game: GameState, settings: Option<GameState>) -> &mut Self {
+        if !self.world().contains_resource::<PausableGames>() {
+            self.init_resource::<PausableGames>()
+                .init_state::<PauseState>()
+                .add_systems(OnEnter(PauseState::Paused), (pause_time, setup_overlay))
+                .add_systems(
+                    OnExit(PauseState::Paused),
+                    (
+                        resume_time,
+                        despawn_with_component::<OnPauseOverlay>,
+                        clear_message,
+                    ),
+                )
+                .add_systems(OnEnter(GameState::Restarting), restart_game)
+                .add_systems(
+                    Update,
+                    (
+                        toggle_pause,
+                        (common_button_system, pause_action)
+                            .chain()
+                            .run_if(in_state(PauseState::Paused)),
+                    ),
+                );
+        }
+        self.world_mut()
+            .resource_mut::<PausableGames>()
+            .0
+            .push((game, settings));
+        self.add_systems(OnExit(game), resume_game)
+    }
+}
+
+// Games that can be paused, with their settings screen
+#[derive(Resource, Default)]
+struct PausableGames(Vec<(GameState, Option<GameState>)>);
+
+impl PausableGames {
+    fn get(&self, game: GameState) -> Option<Option<GameState>> {
+        self.0
+            .iter()
+            .find(|(pausable, _)| *pausable == game)
+            .map(|(_, settings)| *settings)
+    }
+}
+
+// The game to go back to from `GameState::Restarting`
+#[derive(Resource)]
+struct RestartGame(GameState);
+
+/// Shown on the pause overlay until the game is resumed, e.g. why saving
+/// failed.
+#[derive(Resource)]
+pub struct PauseMessage(pub String);
+
+#[derive(Component)]
+struct OnPauseOverlay;
+
+#[derive(Component)]
+enum PauseButtonAction {
+    Resume,
+    Restart,
+    Settings(GameState),
+    Quit,
+}
+
+// Put on the Quit to Settings button after its first press
+#[derive(Component)]
+struct ConfirmQuit;
+
+fn toggle_pause(
+    keys: Res<ButtonInput<KeyCode>>,
+    games: Res<PausableGames>,
+    game_state: Res<State<GameState>>,
+    pause_state: Res<State<PauseState>>,
+    mut next_pause_state: ResMut<NextState<PauseState>>,
+) {
+    if !keys.just_pressed(KeyCode::Escape) || games.get(*game_state.get()).is_none() {
+        return;
+    }
+    next_pause_state.set(match pause_state.get() {
+        PauseState::Running => PauseState::Paused,
+        PauseState::Paused => PauseState::Running,
+    });
+}
+
+fn resume_game(mut next_pause_state: ResMut<NextState<PauseState>>) {
+    next_pause_state.set(PauseState::Running);
+}
+
+fn pause_time(mut time: ResMut<Time<Virtual>>) {
+    time.pause();
+}
+
+fn resume_time(mut time: ResMut<Time<Virtual>>) {
+    time.unpause();
+}
+
+fn clear_message(mut commands: Commands) {
+    commands.remove_resource::<PauseMessage>();
+}
+
+fn setup_overlay(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    games: Res<PausableGames>,
+    game_state: Res<State<GameState>>,
+    message: Option<Res<PauseMessage>>,
+) {
+    let settings = games.get(*game_state.get()).flatten();
+    commands
+        .spawn((
+            Node {
+                position_type: PositionType::Absolute,
+                width: Val::Percent(100.0),
+                height: Val::Percent(100.0),
+                flex_direction: FlexDirection::Column,
+                align_items: AlignItems::Center,
+                justify_content: JustifyContent::Center,
+                ..default()
+            },
+            BackgroundColor(OVERLAY_COLOR),
+            // keeps clicks away from the game underneath
+            FocusPolicy::Block,
+            GlobalZIndex(1),
+            OnPauseOverlay,
+        ))
+        .with_children(|parent| {
+            parent.spawn((
+                Text::new("PAUSED"),
+                TextFont {
+                    font: asset_server.load(FIRASANS_FONT),
+                    font_size: 67.0,
+                    ..default()
+                },
+                TextColor(TEXT_COLOR),
+                Node {
+                    margin: UiRect::all(Val::Px(30.0)),
+                    ..default()
+                },
+            ));
+            if let Some(message) = message {
+                parent.spawn((
+                    Text::new(message.0.clone()),
+                    TextFont {
+                        font: asset_server.load(FIRASANS_FONT),
+                        font_size: 25.0,
+                        ..default()
+                    },
+                    TextColor(TEXT_COLOR),
+                    Node {
+                        margin: UiRect::bottom(Val::Px(20.0)),
+                        ..default()
+                    },
+                ));
+            }
+            parent
+                .spawn((
+                    Node {
+                        flex_direction: FlexDirection::Column,
+                        align_items: AlignItems::Center,
+                        ..default()
+                    },
+                    BackgroundColor(BACKGROUND),
+                ))
+                .with_children(|parent| {
+                    parent.spawn_button(
+                        PauseButtonAction::Resume,
+                        "right.png",
+                        "Resume",
+                        &asset_server,
+                    );
+                    parent.spawn_button(
+                        PauseButtonAction::Restart,
+                        "right.png",
+                        "Restart",
+                        &asset_server,
+                    );
+                    if let Some(settings) = settings {
+                        parent.spawn_button(
+                            PauseButtonAction::Settings(settings),
+                            "wrench.png",
+                            "Quit to Settings",
+                            &asset_server,
+                        );
+                    }
+                    parent.spawn_button(
+                        PauseButtonAction::Quit,
+                        "exitRight.png",
+                        "Quit to Menu",
+                        &asset_server,
+                    );
+                });
+        });
+}
+
+#[allow(clippy::type_complexity)]
+fn pause_action(
+    interaction_query: Query<
+        (
+            &Interaction,
+            &PauseButtonAction,
+            &Children,
+            Entity,
+            Has<ConfirmQuit>,
+        ),
+        Changed<Interaction>,
+    >,
+    mut text_query: Query<&mut Text>,
+    mut commands: Commands,
+    game_state: Res<State<GameState>>,
+    mut next_game_state: ResMut<NextState<GameState>>,
+    mut next_pause_state: ResMut<NextState<PauseState>>,
+) {
+    for (interaction, action, children, entity, confirmed) in &interaction_query {
+        if *interaction != Interaction::Pressed {
+            continue;
+        }
+        match action {
+            PauseButtonAction::Resume => next_pause_state.set(PauseState::Running),
+            PauseButtonAction::Restart => {
+                commands.insert_resource(RestartGame(*game_state.get()));
+                next_game_state.set(GameState::Restarting);
+            }
+            // leaving ends the match, so the first press only asks again
+            PauseButtonAction::Settings(settings) => {
+                if confirmed {
+                    next_game_state.set(*settings);
+                } else {
+                    commands.entity(entity).insert(ConfirmQuit);
+                    let mut texts = text_query.iter_many_mut(children);
+                    while let Some(mut text) = texts.fetch_next() {
+                        **text = "Really Quit?".to_string();
+                    }
+                }
+            }
+            PauseButtonAction::Quit => next_game_state.set(GameState::Menu),
+        }
+    }
+}
+
+fn restart_game(
+    mut commands: Commands,
+    restart: Option<Res<RestartGame>>,
+    mut game_state: ResMut<NextState<GameState>>,
+) {
+    if let Some(restart) = restart {
+        commands.remove_resource::<RestartGame>();
+        game_state.set(restart.0);
+    }
+}
@@ -0,0 +1,276 @@
+// Each team's share of the territory over the whole match. A small chart is drawn
+// with gizmos next to the board while the match runs; once it is over, the
+// result is shown over the board with the chart at full size and a button to
+// export the numbers as CSV.
+use std::{
+    fmt::Write as _,
+    fs,
+    path::PathBuf,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use bevy::prelude::*;
+
+use crate::{
+    common::{FIRASANS_FONT, GAME_DATA_TEXT_COLOR, NORMAL_BUTTON, TEXT_COLOR},
+    region_game::{team_style, RegionSim},
+    region_sim::{MatchOutcome, RegionWorld, TeamId, TICK_SECONDS},
+    utils::despawn_with_component,
+    GameState,
+};
+
+// four samples a second
+const SAMPLE_TICKS: u32 = 16;
+const CHART_DIR: &str = "charts";
+const GRID_COLOR: Color = Color::srgba(1., 1., 1., 0.3);
+const PANEL_COLOR: Color = Color::srgba(0.05, 0.05, 0.1, 0.9);
+// drawn over the bricks and the balls
+const PANEL_Z: f32 = 10.;
+// in world space, which the camera shows one pixel per unit
+const HUD_CHART: Rect = Rect {
+    min: Vec2::new(-540., 120.),
+    max: Vec2::new(-370., 360.),
+};
+const FULL_CHART: Rect = Rect {
+    min: Vec2::new(-400., -200.),
+    max: Vec2::new(400., 200.),
+};
+
+pub struct RegionChartPlugin;
+
+impl Plugin for RegionChartPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(OnEnter(GameState::RegionGame), reset_chart)
+            .add_systems(
+                OnExit(GameState::RegionGame),
+                (despawn_with_component::<OnMatchOverScreen>, cleanup_chart),
+            )
+            .add_systems(
+                FixedUpdate,
+                (sample_territory, sync_match_over)
+                    .chain()
+                    .run_if(resource_exists::<RegionSim>),
+            )
+            .add_systems(
+                Update,
+                (draw_charts, export_button)
+                    .run_if(in_state(GameState::RegionGame))
+                    .run_if(resource_exists::<TerritoryChart>),
+            );
+    }
+}
+
+/// Territory share of each team, in `0..=1`, every `SAMPLE_TICKS` ticks. The
+/// last sample is the current tick.
+#[derive(Resource, Default, Clone)]
+pub struct TerritoryChart {
+    samples: Vec<[f32; 2]>,
+}
+
+impl TerritoryChart {
+    // Replays jump around, so samples are kept by tick rather than appended
+    pub fn sample(&mut self, world: &RegionWorld) {
+        let capturable = world.capturable().max(1) as f32;
+        let share = TeamId::ALL.map(|team| world.count(team) as f32 / capturable);
+        let index = (world.tick() / SAMPLE_TICKS) as usize;
+        self.samples.truncate(index);
+        self.samples.resize(index, share);
+        self.samples.push(share);
+    }
+}
+
+#[derive(Component)]
+struct OnMatchOverScreen;
+
+#[derive(Component)]
+struct ExportButton;
+
+#[derive(Component)]
+struct ExportStatus;
+
+// keeps the chart of a resumed match, which `setup_basedata` puts in
+fn reset_chart(mut commands: Commands) {
+    commands.init_resource::<TerritoryChart>();
+}
+
+fn cleanup_chart(mut commands: Commands) {
+    commands.remove_resource::<TerritoryChart>();
+}
+
+fn sample_territory(sim: Res<RegionSim>, mut chart: ResMut<TerritoryChart>) {
+    chart.sample(&sim);
+}
+
+fn draw_charts(
+    mut gizmos: Gizmos,
+    chart: Res<TerritoryChart>,
+    match_over: Query<(), With<OnMatchOverScreen>>,
+) {
+    let rect = if match_over.is_empty() {
+        HUD_CHART
+    } else {
+        FULL_CHART
+    };
+    gizmos.rect_2d(
+        Isometry2d::from_translation(rect.center()),
+        rect.size(),
+        GRID_COLOR,
+    );
+    gizmos.line_2d(
+        Vec2::new(rect.min.x, rect.center().y),
+        Vec2::new(rect.max.x, rect.center().y),
+        GRID_COLOR,
+    );
+    // at most a point per pixel, long matches would draw far more
+    let step = chart.samples.len().div_ceil(rect.width() as usize).max(1);
+    let last = chart.samples.len().saturating_sub(1).max(1) as f32;
+    for team in TeamId::ALL {
+        let points = chart
+            .samples
+            .iter()
+            .enumerate()
+            .step_by(step)
+            .chain(chart.samples.iter().enumerate().next_back())
+            .map(|(index, share)| {
+                rect.min + rect.size() * Vec2::new(index as f32 / last, share[team.0 as usize])
+            });
+        gizmos.linestrip_2d(points, team_style(team).brick_color);
+    }
+}
+
+fn sync_match_over(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    sim: Res<RegionSim>,
+    screens: Query<Entity, With<OnMatchOverScreen>>,
+) {
+    // the replay viewer can go back to before the end
+    let Some(outcome) = sim.outcome() else {
+        for screen in &screens {
+            commands.entity(screen).despawn_recursive();
+        }
+        return;
+    };
+    if !screens.is_empty() {
+        return;
+    }
+
+    commands.spawn((
+        Sprite::from_color(PANEL_COLOR, FULL_CHART.size() + Vec2::new(60., 180.)),
+        Transform::from_translation(FULL_CHART.center().extend(PANEL_Z)),
+        OnMatchOverScreen,
+    ));
+    let font = asset_server.load(FIRASANS_FONT);
+    let title = match outcome {
+        MatchOutcome::Winner(team) => format!("{} WINS", team_style(team).name),
+        MatchOutcome::Draw => "DRAW".to_string(),
+    };
+    commands
+        .spawn((
+            Node {
+                position_type: PositionType::Absolute,
+                width: Val::Percent(100.0),
+                height: Val::Percent(100.0),
+                flex_direction: FlexDirection::Column,
+                align_items: AlignItems::Center,
+                justify_content: JustifyContent::SpaceBetween,
+                padding: UiRect::vertical(Val::Px(220.0)),
+                ..default()
+            },
+            OnMatchOverScreen,
+        ))
+        .with_children(|parent| {
+            parent.spawn((
+                Text::new(title),
+                TextFont {
+                    font: font.clone(),
+                    font_size: 50.0,
+                    ..default()
+                },
+                TextColor(TEXT_COLOR),
+            ));
+            parent
+                .spawn(Node {
+                    align_items: AlignItems::Center,
+                    ..default()
+                })
+                .with_children(|parent| {
+                    parent
+                        .spawn((
+                            Button,
+                            Node {
+                                padding: UiRect::axes(Val::Px(12.0), Val::Px(4.0)),
+                                ..default()
+                            },
+                            BackgroundColor(NORMAL_BUTTON),
+                            ExportButton,
+                        ))
+                        .with_child((
+                            Text::new("Export CSV"),
+                            TextFont {
+                                font: font.clone(),
+                                font_size: 30.0,
+                                ..default()
+                            },
+                            TextColor(TEXT_COLOR),
+                        ));
+                    parent.spawn((
+                        Text::default(),
+                        TextFont {
+                            font: font.clone(),
+                            font_size: 20.0,
+                            ..default()
+                        },
+                        TextColor(GAME_DATA_TEXT_COLOR),
+                        Node {
+                            margin: UiRect::left(Val::Px(10.0)),
+                            ..default()
+                        },
+                        ExportStatus,
+                    ));
+                });
+        });
+}
+
+fn export_button(
+    interaction_query: Query<&Interaction, (Changed<Interaction>, With<ExportButton>)>,
+    chart: Res<TerritoryChart>,
+    mut status: Query<&mut Text, With<ExportStatus>>,
+) {
+    for interaction in &interaction_query {
+        if *interaction != Interaction::Pressed {
+            continue;
+        }
+        let message = match export_csv(&chart) {
+            Ok(path) => format!("saved to {}", path.display()),
+            Err(error) => format!("could not export: {error}"),
+        };
+        for mut text in &mut status {
+            **text = message.clone();
+        }
+    }
+}
+
+fn export_csv(chart: &TerritoryChart) -> std::io::Result<PathBuf> {
+    let mut csv = String::from("seconds");
+    for team in TeamId::ALL {
+        let _ = write!(csv, ",{}", team_style(team).name.to_lowercase());
+    }
+    csv.push('\n');
+    for (index, share) in chart.samples.iter().enumerate() {
+        let seconds = index as f32 * SAMPLE_TICKS as f32 * TICK_SECONDS;
+        let _ = write!(csv, "{seconds:.2}");
+        for team in TeamId::ALL {
+            let _ = write!(csv, ",{:.2}", share[team.0 as usize] * 100.);
+        }
+        csv.push('\n');
+    }
+
+    let seconds = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |time| time.as_secs());
+    let path = PathBuf::from(CHART_DIR).join(format!("territory-{seconds:012}.csv"));
+    fs::create_dir_all(CHART_DIR)?;
+    fs::write(&path, csv)?;
+    Ok(path)
+}
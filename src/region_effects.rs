@@ -0,0 +1,307 @@
+// Short effects on top of the Region Battle board: captured bricks pop and burst
+// into particles of the capturing team, balls squash when they bounce and
+// leave a fading trail, and big swings shake the camera. Everything is drawn
+// with plain sprites and gizmos, and each effect can be turned off on the
+// options screen.
+use std::collections::VecDeque;
+
+use bevy::prelude::*;
+use rand::Rng;
+
+use crate::{
+    region_game::{team_style, BallBounced, BrickCaptured, RegionBall, RegionSim},
+    region_instant_replay::InstantReplay,
+    utils::despawn_with_component,
+    GameState,
+};
+
+// over the bricks and the heatmap, under the balls
+const POP_Z: f32 = 0.7;
+const PARTICLE_Z: f32 = 0.8;
+const POP_SECONDS: f32 = 0.25;
+const POP_GROWTH: f32 = 0.6;
+const PARTICLES_PER_CAPTURE: usize = 5;
+const PARTICLE_SECONDS: f32 = 0.45;
+const PARTICLE_SIZE: f32 = 4.;
+const PARTICLE_SPEED: std::ops::Range<f32> = 60.0..160.0;
+// fraction of its speed a particle keeps after a second
+const PARTICLE_DRAG: f32 = 0.05;
+// a fast match captures far more cells than are worth drawing
+const MAX_POPS: usize = 200;
+const MAX_PARTICLES: usize = 400;
+const SQUASH_SECONDS: f32 = 0.15;
+const SQUASH: f32 = 0.35;
+const TRAIL_SAMPLES: usize = 12;
+const TRAIL_ALPHA: f32 = 0.5;
+// a ball further than this from its last sample was moved by a replay seek
+const TRAIL_JUMP: f32 = 60.;
+// a single capture barely moves the camera, a paint bomb shakes it
+const TRAUMA_PER_CAPTURE: f32 = 0.05;
+const TRAUMA_DECAY: f32 = 2.;
+const SHAKE_PIXELS: f32 = 8.;
+
+pub struct RegionEffectsPlugin;
+
+impl Plugin for RegionEffectsPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<EffectSettings>()
+            .add_systems(OnEnter(GameState::RegionGame), reset_shake)
+            .add_systems(
+                OnExit(GameState::RegionGame),
+                (despawn_with_component::<OnEffect>, reset_camera),
+            )
+            .add_systems(
+                Update,
+                (
+                    capture_effects,
+                    animate_pops,
+                    animate_particles,
+                    squash_balls.run_if(not(resource_exists::<InstantReplay>)),
+                    draw_trails,
+                    shake_camera,
+                )
+                    .run_if(in_state(GameState::RegionGame))
+                    .run_if(resource_exists::<RegionSim>),
+            );
+    }
+}
+
+/// Which effects are shown, picked on the options screen.
+#[derive(Resource, Debug, Clone, Copy)]
+pub struct EffectSettings {
+    pub brick_pop: bool,
+    pub particles: bool,
+    pub ball_squash: bool,
+    pub trails: bool,
+    pub camera_shake: bool,
+}
+
+impl Default for EffectSettings {
+    fn default() -> Self {
+        Self {
+            brick_pop: true,
+            particles: true,
+            ball_squash: true,
+            trails: true,
+            camera_shake: true,
+        }
+    }
+}
+
+#[derive(Component)]
+struct OnEffect;
+
+#[derive(Component)]
+struct Pop(Timer);
+
+#[derive(Component)]
+struct Particle {
+    velocity: Vec2,
+    life: Timer,
+}
+
+/// How hard the camera shakes, decaying back to `0`.
+#[derive(Resource, Default)]
+struct CameraShake {
+    trauma: f32,
+}
+
+fn reset_shake(mut commands: Commands) {
+    commands.init_resource::<CameraShake>();
+}
+
+fn reset_camera(mut commands: Commands, mut cameras: Query<&mut Transform, With<Camera2d>>) {
+    commands.remove_resource::<CameraShake>();
+    for mut transform in &mut cameras {
+        transform.translation.x = 0.;
+        transform.translation.y = 0.;
+    }
+}
+
+fn capture_effects(
+    mut commands: Commands,
+    mut captures: EventReader<BrickCaptured>,
+    settings: Res<EffectSettings>,
+    sim: Res<RegionSim>,
+    mut shake: Option<ResMut<CameraShake>>,
+    pops: Query<(), With<Pop>>,
+    particles: Query<(), With<Particle>>,
+) {
+    let mut rng = rand::thread_rng();
+    let mut pop_count = pops.iter().count();
+    let mut particle_count = particles.iter().count();
+    for capture in captures.read() {
+        let Some(owner) = capture.to.owner() else {
+            continue;
+        };
+        let color = team_style(owner).brick_color;
+        let grid = sim.grid();
+        let center = grid.center(capture.cell);
+        if settings.camera_shake {
+            if let Some(shake) = shake.as_mut() {
+                shake.trauma = (shake.trauma + TRAUMA_PER_CAPTURE).min(1.);
+            }
+        }
+        if settings.brick_pop && pop_count < MAX_POPS {
+            pop_count += 1;
+            commands.spawn((
+                Sprite::from_color(color, Vec2::splat(grid.brick_size)),
+                Transform::from_translation(center.extend(POP_Z)),
+                Pop(Timer::from_seconds(POP_SECONDS, TimerMode::Once)),
+                OnEffect,
+            ));
+        }
+        if !settings.particles {
+            continue;
+        }
+        for _ in 0..PARTICLES_PER_CAPTURE {
+            if particle_count >= MAX_PARTICLES {
+                break;
+            }
+            particle_count += 1;
+            let direction = Vec2::from_angle(rng.gen_range(0.0..std::f32::consts::TAU));
+            commands.spawn((
+                Sprite::from_color(color, Vec2::splat(PARTICLE_SIZE)),
+                Transform::from_translation(center.extend(PARTICLE_Z)),
+                Particle {
+                    velocity: direction * rng.gen_range(PARTICLE_SPEED),
+                    life: Timer::from_seconds(PARTICLE_SECONDS, TimerMode::Once),
+                },
+                OnEffect,
+            ));
+        }
+    }
+}
+
+// Effects run on real time, so they look the same at any simulation speed
+fn animate_pops(
+    mut commands: Commands,
+    time: Res<Time<Real>>,
+    mut pops: Query<(Entity, &mut Pop, &mut Sprite, &mut Transform)>,
+) {
+    for (entity, mut pop, mut sprite, mut transform) in &mut pops {
+        if pop.0.tick(time.delta()).finished() {
+            commands.entity(entity).despawn();
+            continue;
+        }
+        let progress = pop.0.fraction();
+        // ease out, quick at first and settling at the end
+        let eased = 1. - (1. - progress).powi(3);
+        transform.scale = Vec3::splat(1. + POP_GROWTH * eased);
+        sprite.color.set_alpha(0.8 * (1. - progress));
+    }
+}
+
+fn animate_particles(
+    mut commands: Commands,
+    time: Res<Time<Real>>,
+    mut particles: Query<(Entity, &mut Particle, &mut Sprite, &mut Transform)>,
+) {
+    let seconds = time.delta_secs();
+    for (entity, mut particle, mut sprite, mut transform) in &mut particles {
+        if particle.life.tick(time.delta()).finished() {
+            commands.entity(entity).despawn();
+            continue;
+        }
+        particle.velocity *= PARTICLE_DRAG.powf(seconds);
+        transform.translation += (particle.velocity * seconds).extend(0.);
+        sprite.color.set_alpha(1. - particle.life.fraction());
+    }
+}
+
+// Stretches each ball across its direction of travel for a moment after a
+// bounce. Sets the whole scale, which `sync_balls` only sets on fixed ticks.
+fn squash_balls(
+    mut bounces: EventReader<BallBounced>,
+    settings: Res<EffectSettings>,
+    sim: Res<RegionSim>,
+    time: Res<Time<Real>>,
+    // how squashed each ball is, from 1 right after a bounce down to 0
+    mut squash: Local<Vec<f32>>,
+    mut balls: Query<(&mut Transform, &RegionBall)>,
+) {
+    squash.resize(sim.balls().len(), 0.);
+    for amount in squash.iter_mut() {
+        *amount = (*amount - time.delta_secs() / SQUASH_SECONDS).max(0.);
+    }
+    for bounce in bounces.read() {
+        if settings.ball_squash {
+            if let Some(amount) = squash.get_mut(bounce.ball) {
+                *amount = 1.;
+            }
+        }
+    }
+    for (mut transform, ball) in &mut balls {
+        let (Some(state), Some(amount)) = (sim.balls().get(ball.0), squash.get(ball.0)) else {
+            continue;
+        };
+        transform.rotation = Quat::from_rotation_z(state.velocity.to_angle());
+        transform.scale.x = state.radius * (1. - SQUASH * amount);
+        transform.scale.y = state.radius * (1. + SQUASH * 0.5 * amount);
+    }
+}
+
+fn draw_trails(
+    mut gizmos: Gizmos,
+    settings: Res<EffectSettings>,
+    sim: Res<RegionSim>,
+    // recent positions of each ball, newest last
+    mut trails: Local<Vec<VecDeque<Vec2>>>,
+    balls: Query<(&Transform, &RegionBall, &ViewVisibility)>,
+) {
+    if !settings.trails {
+        trails.clear();
+        return;
+    }
+    trails.resize(sim.balls().len(), VecDeque::new());
+    for (transform, ball, visibility) in &balls {
+        let (Some(trail), Some(state)) = (trails.get_mut(ball.0), sim.balls().get(ball.0)) else {
+            continue;
+        };
+        let position = transform.translation.truncate();
+        if !visibility.get()
+            || trail
+                .back()
+                .is_some_and(|last| last.distance(position) > TRAIL_JUMP)
+        {
+            trail.clear();
+        }
+        trail.push_back(position);
+        if trail.len() > TRAIL_SAMPLES {
+            trail.pop_front();
+        }
+        let color = team_style(state.team).ball_color;
+        let last = trail.len().saturating_sub(1).max(1) as f32;
+        gizmos.linestrip_gradient_2d(
+            trail.iter().enumerate().map(|(index, point)| {
+                (*point, color.with_alpha(TRAIL_ALPHA * index as f32 / last))
+            }),
+        );
+    }
+}
+
+fn shake_camera(
+    settings: Res<EffectSettings>,
+    time: Res<Time<Real>>,
+    shake: Option<ResMut<CameraShake>>,
+    mut cameras: Query<&mut Transform, With<Camera2d>>,
+) {
+    let Some(mut shake) = shake else {
+        return;
+    };
+    if !settings.camera_shake {
+        shake.trauma = 0.;
+    }
+    shake.trauma = (shake.trauma - TRAUMA_DECAY * time.delta_secs()).max(0.);
+    // squared, so small swings stay still and only big ones shake
+    let strength = shake.trauma * shake.trauma * SHAKE_PIXELS;
+    let offset = if strength > 0. {
+        Vec2::from_angle(rand::thread_rng().gen_range(0.0..std::f32::consts::TAU)) * strength
+    } else {
+        Vec2::ZERO
+    };
+    for mut transform in &mut cameras {
+        transform.translation.x = offset.x;
+        transform.translation.y = offset.y;
+    }
+}
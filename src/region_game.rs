@@ -1,31 +1,122 @@
 use bevy::{
-    color::palettes::css::{GRAY, TOMATO},
-    math::bounding::{Aabb2d, BoundingCircle, BoundingVolume, IntersectsVolume},
+    color::{
+        palettes::{
+            basic::{OLIVE, PURPLE},
+            css::{GRAY, TOMATO},
+        },
+        ColorToPacked,
+    },
+    ecs::system::SystemParam,
+    image::ImageSampler,
+    math::bounding::{Aabb2d, BoundingVolume},
     prelude::*,
+    render::{
+        render_asset::RenderAssetUsages,
+        render_resource::{Extent3d, TextureDimension, TextureFormat},
+    },
+    window::PrimaryWindow,
 };
+use rand::{rngs::StdRng, Rng, SeedableRng};
 
 use crate::{
-    common::{FIRASANS_FONT, NORMAL_BUTTON, TEXT_COLOR},
+    common::{FIRASANS_FONT, GAME_DATA_TEXT_COLOR, NORMAL_BUTTON, TEXT_COLOR},
+    pause::{PauseMenuExt, PauseState},
+    region_heatmap::count_captures,
+    region_instant_replay::{record_history, InstantReplay, MatchHistory},
+    region_power_ups::PowerUpHud,
+    region_replay::{step_replay, MatchRecorder, ReplayViewer},
+    region_save::ResumedMatch,
+    region_setup::RegionSettings,
+    region_sim::{
+        Ball, Cell, Grid, History, MatchOutcome, Recorder, RegionWorld, ReplayInput, SteeringInput,
+        Surface, TeamId, WorldEvent, ARMOR_HITS, TICK_SECONDS,
+    },
     utils::{common_button_system, despawn_with_component},
     GameState,
 };
 
-const BRICK_WIDTH: i32 = 20;
-const BRICK_COUNT_WIDTH: i32 = 30;
+const WALL_COLOR: Color = Color::srgb(0.8, 0.8, 0.8);
+const NEUTRAL_COLOR: Color = Color::srgb(0.93, 0.9, 0.8);
+// how much darker an armored brick gets per hit point lost, on top of one step
+// for being armored at all
+const ARMOR_SHADE: f32 = 0.15;
+// armored brick images, the frame and cracks are multiplied with the brick color
+const ARMOR_TEXELS: u32 = 20;
+const ARMOR_FRAME: [u8; 4] = [190, 190, 190, 255];
+const ARMOR_CRACK: [u8; 4] = [70, 70, 70, 255];
 
-const MID_POS: i32 = BRICK_COUNT_WIDTH / 2;
+const BOOST_BAR_BACKGROUND: Color = Color::srgb(0.25, 0.25, 0.25);
 
-const WALL_COLOR: Color = Color::srgb(0.8, 0.8, 0.8);
+/// Keys a player steers their team's balls with in a two-player match.
+struct SteeringKeys {
+    team: TeamId,
+    up: KeyCode,
+    down: KeyCode,
+    left: KeyCode,
+    right: KeyCode,
+    boost: KeyCode,
+}
+
+const STEERING_KEYS: [SteeringKeys; 2] = [
+    SteeringKeys {
+        team: TeamId::RED,
+        up: KeyCode::KeyW,
+        down: KeyCode::KeyS,
+        left: KeyCode::KeyA,
+        right: KeyCode::KeyD,
+        boost: KeyCode::ShiftLeft,
+    },
+    SteeringKeys {
+        team: TeamId::BLUE,
+        up: KeyCode::ArrowUp,
+        down: KeyCode::ArrowDown,
+        left: KeyCode::ArrowLeft,
+        right: KeyCode::ArrowRight,
+        boost: KeyCode::ShiftRight,
+    },
+];
 
-const WALL_THICKNESS: f32 = 40.0;
-// x coordinates
-const LEFT_WALL: f32 = (-MID_POS * BRICK_WIDTH) as f32;
-const RIGHT_WALL: f32 = -1. * LEFT_WALL;
-// y coordinates
-const BOTTOM_WALL: f32 = LEFT_WALL;
-const TOP_WALL: f32 = RIGHT_WALL;
+/// How a team is drawn: the color of the bricks it owns, the color of its ball
+/// and where its score board sits.
+pub struct TeamStyle {
+    pub name: &'static str,
+    pub brick_color: Color,
+    pub ball_color: Color,
+    pub board_on_left: bool,
+}
+
+const TEAM_STYLES: [TeamStyle; 2] = [
+    TeamStyle {
+        name: "RED",
+        brick_color: Color::Srgba(TOMATO),
+        ball_color: Color::Srgba(PURPLE),
+        board_on_left: true,
+    },
+    TeamStyle {
+        name: "BLUE",
+        brick_color: Color::Srgba(GRAY),
+        ball_color: Color::Srgba(OLIVE),
+        board_on_left: false,
+    },
+];
+
+pub fn team_style(team: TeamId) -> &'static TeamStyle {
+    &TEAM_STYLES[team.0 as usize % TEAM_STYLES.len()]
+}
 
-const GAME_DATA_TEXT_COLOR: Color = Color::srgb(0., 0.22, 0.76);
+fn cell_color(cell: Cell) -> Color {
+    match cell {
+        Cell::Team(team) => team_style(team).brick_color,
+        Cell::Neutral => NEUTRAL_COLOR,
+        Cell::Armored { owner, hits } => {
+            let base = owner.map_or(NEUTRAL_COLOR, |team| team_style(team).brick_color);
+            let lost = ARMOR_HITS.saturating_sub(hits) as f32;
+            base.mix(&Color::BLACK, ARMOR_SHADE * (1. + lost))
+        }
+        Cell::Wall => WALL_COLOR,
+        Cell::Void => Color::NONE,
+    }
+}
 
 #[derive(Component)]
 struct Collider;
@@ -42,55 +133,19 @@ struct WallBundle {
 #[derive(Component)]
 struct ReturnButton;
 
-/// Which side of the arena is this wall located on?
-enum WallLocation {
-    Left,
-    Right,
-    Bottom,
-    Top,
-}
-
-impl WallLocation {
-    fn position(&self) -> Vec2 {
-        match self {
-            WallLocation::Left => Vec2::new(LEFT_WALL, 0.),
-            WallLocation::Right => Vec2::new(RIGHT_WALL, 0.),
-            WallLocation::Bottom => Vec2::new(0., BOTTOM_WALL),
-            WallLocation::Top => Vec2::new(0., TOP_WALL),
-        }
-    }
-
-    fn size(&self) -> Vec2 {
-        let arena_height = TOP_WALL - BOTTOM_WALL;
-        let arena_width = RIGHT_WALL - LEFT_WALL;
-        // Make sure we haven't messed up our constants
-        assert!(arena_height > 0.0);
-        assert!(arena_width > 0.0);
-
-        match self {
-            WallLocation::Left | WallLocation::Right => {
-                Vec2::new(WALL_THICKNESS, arena_height + WALL_THICKNESS)
-            }
-            WallLocation::Bottom | WallLocation::Top => {
-                Vec2::new(arena_width + WALL_THICKNESS, WALL_THICKNESS)
-            }
-        }
-    }
-}
-
 impl WallBundle {
     // This "builder method" allows us to reuse logic across our wall entities,
     // making our code easier to read and less prone to bugs when we change the logic
-    fn new(location: WallLocation) -> WallBundle {
+    fn new(wall: &Aabb2d) -> WallBundle {
         WallBundle {
             transform: Transform {
                 // We need to convert our Vec2 into a Vec3, by giving it a z-coordinate
                 // This is used to determine the order of our sprites
-                translation: location.position().extend(0.0),
+                translation: wall.center().extend(0.0),
                 // The z-scale of 2D objects must always be 1.0,
                 // or their ordering will be affected in surprising ways.
                 // See https://github.com/bevyengine/bevy/issues/4149
-                scale: location.size().extend(1.0),
+                scale: (wall.half_size() * 2.).extend(1.0),
                 ..default()
             },
             sprite: Sprite {
@@ -102,309 +157,488 @@ impl WallBundle {
     }
 }
 
-#[derive(Debug, PartialEq, Eq)]
-enum BrickColor {
-    Red,
-    Blue,
+pub struct RegionGamePlugin;
+
+/// The simulation that drives the match. Everything drawn on screen is mirrored
+/// from it.
+#[derive(Resource, Deref, DerefMut)]
+pub struct RegionSim(RegionWorld);
+
+/// How the board is drawn. Both backends only mirror `RegionWorld::cells`, so the
+/// simulation does not know which one is active.
+#[derive(Resource, Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum BoardBackend {
+    /// One sprite entity per brick.
+    #[default]
+    Sprites,
+    /// A single image with one texel per brick, for boards too large for a sprite
+    /// per brick.
+    Texture,
 }
 
-pub struct RegionGamePlugin;
+/// Sprite entity for each cell of the board, indexed like `RegionWorld::cells`.
+#[derive(Resource)]
+struct BrickEntities(Vec<Entity>);
+
+#[derive(Component)]
+struct Brick;
 
+/// The sprite showing the whole board when `BoardBackend::Texture` is active.
 #[derive(Component)]
-struct Brick(BrickColor);
+struct BoardTexture(Handle<Image>);
+
+/// Mirrors the ball with the same index in `RegionWorld::balls`.
+#[derive(Component)]
+pub struct RegionBall(pub usize);
 
 #[derive(Component)]
 struct PlayBoard;
+/// Barrier charges and cooldown of a team.
 #[derive(Component)]
-struct PlayerScore(BrickColor);
-
-trait Player {
-    const BREAK_COLOR: BrickColor;
-    const RENDER_COLOR: Color;
-    fn x(&self) -> f32;
-    fn y(&self) -> f32;
-    fn set_x(&mut self, x: f32);
-    fn set_y(&mut self, y: f32);
-    fn place_board(commands: &mut Commands, asset_server: &Res<AssetServer>);
-    fn place_player(
-        commands: &mut Commands,
-        meshes: &mut ResMut<Assets<Mesh>>,
-        materials: &mut ResMut<Assets<ColorMaterial>>,
-    );
-}
+struct BarrierHud(TeamId);
+/// Mirrors the barrier with the same id in `RegionWorld::barriers`.
+#[derive(Component)]
+struct BarrierWall(u32);
+
+/// Barriers clicked since the last fixed tick, placed at the start of the next
+/// one.
+#[derive(Resource, Default)]
+struct PendingBarriers(Vec<(TeamId, Vec2, bool)>);
 
+/// The filled part of a team's boost meter.
+#[derive(Component)]
+struct BoostBar(TeamId);
+/// Pace of the team's balls and the time left, if the match has a limit.
+#[derive(Component)]
+struct SpeedHud(TeamId);
 #[derive(Component)]
-struct RedPlayer {
-    x: f32,
-    y: f32,
+struct PlayerScore(TeamId);
+
+/// A ball took a cell, sent after the tick it happened in.
+#[derive(Event, Debug, Clone, Copy)]
+pub struct BrickCaptured {
+    pub cell: usize,
+    pub from: Cell,
+    pub to: Cell,
+    /// Index into `RegionWorld::balls`.
+    pub ball: usize,
 }
 
-impl RedPlayer {
-    fn new() -> Self {
-        Self { x: 100., y: 100. }
-    }
+/// A ball bounced off a wall, a barrier, a brick or another ball.
+#[derive(Event, Debug, Clone, Copy)]
+pub struct BallBounced {
+    pub ball: usize,
+    pub surface: Surface,
 }
 
-const TOMOTA_COLOR: Color = Color::Srgba(TOMATO);
+/// The match has been decided. Sent again when a replay plays past the end
+/// after going back before it.
+#[derive(Event, Debug, Clone, Copy)]
+pub struct MatchEnded {
+    /// `None` for a draw.
+    pub winner: Option<TeamId>,
+}
 
-impl Player for RedPlayer {
-    const BREAK_COLOR: BrickColor = BrickColor::Red;
-    const RENDER_COLOR: Color = TOMOTA_COLOR;
-    fn x(&self) -> f32 {
-        self.x
-    }
-    fn y(&self) -> f32 {
-        self.y
-    }
-    fn set_x(&mut self, x: f32) {
-        self.x = x;
-    }
-    fn set_y(&mut self, y: f32) {
-        self.y = y;
+/// Whether the match screen shows a live match or plays a replay.
+#[derive(SubStates, Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+#[source(GameState = GameState::RegionGame)]
+pub(crate) enum RegionMode {
+    #[default]
+    Live,
+    Replay,
+}
+
+impl Plugin for RegionGamePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_sub_state::<RegionMode>()
+            .add_pause_menu(GameState::RegionGame, Some(GameState::RegionSetup))
+            .init_resource::<BoardBackend>()
+            .init_resource::<PendingBarriers>()
+            .add_event::<BrickCaptured>()
+            .add_event::<BallBounced>()
+            .add_event::<MatchEnded>()
+            .add_systems(
+                OnEnter(GameState::RegionGame),
+                (setup_basedata, setup_player).chain(),
+            )
+            .add_systems(
+                OnExit(GameState::RegionGame),
+                (
+                    despawn_with_component::<Brick>,
+                    despawn_with_component::<BoardTexture>,
+                    despawn_with_component::<Collider>,
+                    despawn_with_component::<PlayBoard>,
+                    despawn_with_component::<ReturnButton>,
+                    despawn_with_component::<RegionBall>,
+                    cleanup_sim,
+                ),
+            )
+            .add_systems(
+                FixedUpdate,
+                (
+                    (read_steering, place_barriers, step_sim, record_history)
+                        .chain()
+                        .run_if(in_state(RegionMode::Live))
+                        .run_if(not(resource_exists::<InstantReplay>)),
+                    step_replay.run_if(in_state(RegionMode::Replay)),
+                    send_world_events,
+                    log_stalemates,
+                    log_match_events,
+                    count_captures,
+                    sync_bricks.run_if(resource_equals(BoardBackend::Sprites)),
+                    sync_board_texture.run_if(resource_equals(BoardBackend::Texture)),
+                    sync_barriers,
+                    spawn_split_balls,
+                    sync_balls.run_if(not(resource_exists::<InstantReplay>)),
+                    handle_score_update,
+                    sync_boost_bars,
+                    update_barrier_hud,
+                    update_speed_hud,
+                )
+                    .chain()
+                    .run_if(in_state(GameState::RegionGame)),
+            )
+            .add_systems(
+                Update,
+                (
+                    common_button_system,
+                    menu_action,
+                    click_barriers
+                        .run_if(in_state(RegionMode::Live))
+                        .run_if(in_state(PauseState::Running)),
+                )
+                    .chain()
+                    .run_if(in_state(GameState::RegionGame)),
+            );
     }
-    fn place_board(commands: &mut Commands, asset_server: &Res<AssetServer>) {
+}
+
+fn place_board(
+    commands: &mut Commands,
+    asset_server: &Res<AssetServer>,
+    team: TeamId,
+    world: &RegionWorld,
+) {
+    let style = team_style(team);
+    let node = if style.board_on_left {
+        Node {
+            justify_content: JustifyContent::Center,
+            align_self: AlignSelf::Start,
+            position_type: PositionType::Absolute,
+            left: Val::Px(10.),
+            top: Val::Px(470.0),
+            ..Default::default()
+        }
+    } else {
+        Node {
+            justify_content: JustifyContent::Center,
+            align_content: AlignContent::End,
+            align_items: AlignItems::End,
+            align_self: AlignSelf::End,
+            position_type: PositionType::Absolute,
+            right: Val::Px(10.),
+            top: Val::Px(470.0),
+            ..Default::default()
+        }
+    };
+    commands.spawn((
+        Text::default(),
+        TextFont {
+            font: asset_server.load(FIRASANS_FONT),
+            font_size: 20.0,
+            ..Default::default()
+        },
+        TextColor(GAME_DATA_TEXT_COLOR),
+        Node {
+            top: Val::Px(420.0),
+            ..node.clone()
+        },
+        PlayBoard,
+        SpeedHud(team),
+    ));
+    if world.boost_meter(team).is_some() {
         commands
             .spawn((
-                Text::new("RED SCORE"),
-                TextFont {
-                    font: asset_server.load(FIRASANS_FONT),
-                    font_size: 20.0,
-                    ..Default::default()
-                },
-                TextColor(GAME_DATA_TEXT_COLOR),
                 Node {
-                    justify_content: JustifyContent::Center,
-                    align_self: AlignSelf::Start,
-                    position_type: PositionType::Absolute,
-                    left: Val::Px(10.),
-                    top: Val::Px(470.0),
-                    ..Default::default()
+                    top: Val::Px(550.0),
+                    width: Val::Px(150.0),
+                    height: Val::Px(12.0),
+                    ..node.clone()
                 },
+                BackgroundColor(BOOST_BAR_BACKGROUND),
                 PlayBoard,
             ))
             .with_child((
-                Text::new("0"),
-                TextFont {
-                    font: asset_server.load(FIRASANS_FONT),
-                    font_size: 42.0,
-                    ..Default::default()
-                },
-                TextColor(GAME_DATA_TEXT_COLOR),
                 Node {
-                    justify_content: JustifyContent::Center,
-                    align_content: AlignContent::Center,
-                    position_type: PositionType::Relative,
-                    top: Val::Px(50.0),
-                    ..Default::default()
+                    width: Val::Percent(100.0),
+                    height: Val::Percent(100.0),
+                    ..default()
                 },
-                PlayerScore(Self::BREAK_COLOR),
+                BackgroundColor(style.ball_color),
+                BoostBar(team),
             ));
     }
-    fn place_player(
-        commands: &mut Commands,
-        meshes: &mut ResMut<Assets<Mesh>>,
-        materials: &mut ResMut<Assets<ColorMaterial>>,
-    ) {
-        const LEN: i32 = BRICK_COUNT_WIDTH / 4;
-        const RED_X: i32 = -MID_POS * BRICK_WIDTH + LEN * BRICK_WIDTH;
-        use bevy::color::palettes::basic::PURPLE;
+    if world.barrier_stock(team).is_some() {
         commands.spawn((
-            Mesh2d(meshes.add(Circle::new(10.))),
-            Transform {
-                translation: Vec3 {
-                    x: RED_X as f32,
-                    y: 0.,
-                    z: 1.,
-                },
-                scale: Vec3 {
-                    x: 1.,
-                    y: 1.,
-                    z: 2.,
-                },
-                ..default()
+            Text::default(),
+            TextFont {
+                font: asset_server.load(FIRASANS_FONT),
+                font_size: 20.0,
+                ..Default::default()
+            },
+            TextColor(GAME_DATA_TEXT_COLOR),
+            Node {
+                top: Val::Px(570.0),
+                ..node.clone()
             },
-            MeshMaterial2d(materials.add(Color::from(PURPLE))),
-            RedPlayer::new(),
+            PlayBoard,
+            BarrierHud(team),
         ));
     }
+    // active power-ups of the team, listed under the score
+    commands.spawn((
+        Text::default(),
+        TextFont {
+            font: asset_server.load(FIRASANS_FONT),
+            font_size: 20.0,
+            ..Default::default()
+        },
+        TextColor(GAME_DATA_TEXT_COLOR),
+        Node {
+            top: Val::Px(600.0),
+            ..node.clone()
+        },
+        PlayBoard,
+        PowerUpHud(team),
+    ));
+    commands
+        .spawn((
+            Text::new(format!("{} SCORE", style.name)),
+            TextFont {
+                font: asset_server.load(FIRASANS_FONT),
+                font_size: 20.0,
+                ..Default::default()
+            },
+            TextColor(GAME_DATA_TEXT_COLOR),
+            node,
+            PlayBoard,
+        ))
+        .with_child((
+            Text::new("0"),
+            TextFont {
+                font: asset_server.load(FIRASANS_FONT),
+                font_size: 42.0,
+                ..Default::default()
+            },
+            TextColor(GAME_DATA_TEXT_COLOR),
+            Node {
+                justify_content: JustifyContent::Center,
+                align_content: AlignContent::Center,
+                position_type: PositionType::Relative,
+                top: Val::Px(50.0),
+                ..Default::default()
+            },
+            PlayerScore(team),
+        ));
 }
 
-#[derive(Component)]
-struct BluePlayer {
-    x: f32,
-    y: f32,
+fn spawn_brick_sprites(commands: &mut Commands, world: &RegionWorld, armor: &ArmorImages) {
+    let grid = world.grid();
+    let bricks = world
+        .cells()
+        .iter()
+        .enumerate()
+        .map(|(index, cell)| {
+            commands
+                .spawn((
+                    Sprite {
+                        color: cell_color(*cell),
+                        image: armor.image(*cell),
+                        custom_size: Some(Vec2::ONE),
+                        ..default()
+                    },
+                    Transform {
+                        scale: Vec3 {
+                            x: grid.brick_size,
+                            y: grid.brick_size,
+                            z: 0.,
+                        },
+                        translation: grid.center(index).extend(0.),
+                        ..default()
+                    },
+                    Brick,
+                ))
+                .id()
+        })
+        .collect();
+    commands.insert_resource(BrickEntities(bricks));
 }
-const GRAY_COLOR: Color = Color::Srgba(GRAY);
-impl Player for BluePlayer {
-    const BREAK_COLOR: BrickColor = BrickColor::Blue;
-    const RENDER_COLOR: Color = GRAY_COLOR;
-    fn x(&self) -> f32 {
-        self.x
-    }
-    fn y(&self) -> f32 {
-        self.y
-    }
-    fn set_x(&mut self, x: f32) {
-        self.x = x;
-    }
-    fn set_y(&mut self, y: f32) {
-        self.y = y;
-    }
-    fn place_board(commands: &mut Commands, asset_server: &Res<AssetServer>) {
-        commands
-            .spawn((
-                Text::new("BLUE SCORE"),
-                TextFont {
-                    font: asset_server.load(FIRASANS_FONT),
-                    font_size: 20.0,
-                    ..Default::default()
-                },
-                TextColor(GAME_DATA_TEXT_COLOR),
-                Node {
-                    justify_content: JustifyContent::Center,
-                    align_content: AlignContent::End,
-                    align_items: AlignItems::End,
-                    align_self: AlignSelf::End,
-                    position_type: PositionType::Absolute,
-                    right: Val::Px(10.),
-                    top: Val::Px(470.0),
-                    ..Default::default()
-                },
-                PlayBoard,
-            ))
-            .with_child((
-                Text::new("0"),
-                TextFont {
-                    font: asset_server.load(FIRASANS_FONT),
-                    font_size: 42.0,
-                    ..Default::default()
-                },
-                TextColor(GAME_DATA_TEXT_COLOR),
-                Node {
-                    justify_content: JustifyContent::Center,
-                    position_type: PositionType::Relative,
-                    top: Val::Px(50.0),
-                    ..Default::default()
-                },
-                PlayerScore(Self::BREAK_COLOR),
-            ));
+
+fn spawn_board_texture(commands: &mut Commands, images: &mut Assets<Image>, world: &RegionWorld) {
+    let grid = world.grid();
+    let mut image = Image::new_fill(
+        Extent3d {
+            width: grid.columns as u32,
+            height: grid.rows as u32,
+            depth_or_array_layers: 1,
+        },
+        TextureDimension::D2,
+        &[0, 0, 0, 255],
+        TextureFormat::Rgba8UnormSrgb,
+        RenderAssetUsages::default(),
+    );
+    // keep the bricks square instead of blurring them together when scaled up
+    image.sampler = ImageSampler::nearest();
+    for (index, cell) in world.cells().iter().enumerate() {
+        paint_texel(&mut image, grid, index, cell_color(*cell));
     }
 
-    fn place_player(
-        commands: &mut Commands,
-        meshes: &mut ResMut<Assets<Mesh>>,
-        materials: &mut ResMut<Assets<ColorMaterial>>,
-    ) {
-        const LEN: i32 = BRICK_COUNT_WIDTH / 4;
-        const RED_X: i32 = -MID_POS * BRICK_WIDTH + LEN * BRICK_WIDTH;
-        const BLUE_X: i32 = -RED_X;
-        use bevy::color::palettes::basic::OLIVE;
-        commands.spawn((
-            Mesh2d(meshes.add(Circle::new(10.))),
-            Transform {
-                translation: Vec3 {
-                    x: BLUE_X as f32,
-                    y: 0.,
-                    z: 1.,
-                },
-                scale: Vec3 {
-                    x: 1.,
-                    y: 1.,
-                    z: 2.,
-                },
-                ..default()
+    let handle = images.add(image);
+    commands.spawn((
+        Sprite {
+            image: handle.clone(),
+            custom_size: Some(Vec2::new(grid.columns as f32, grid.rows as f32) * grid.brick_size),
+            ..default()
+        },
+        Transform::default(),
+        BoardTexture(handle),
+    ));
+}
+
+fn paint_texel(image: &mut Image, grid: Grid, cell: usize, color: Color) {
+    let (column, row) = grid.coords(cell);
+    // image rows run top to bottom, grid rows bottom to top
+    put_texel(
+        image,
+        column as u32,
+        (grid.rows - 1 - row) as u32,
+        color.to_srgba().to_u8_array(),
+    );
+}
+
+fn put_texel(image: &mut Image, x: u32, y: u32, rgba: [u8; 4]) {
+    let texel = ((y * image.width() + x) * 4) as usize;
+    image.data[texel..texel + 4].copy_from_slice(&rgba);
+}
+
+/// Sprite images for armored bricks, indexed by the hits they have taken: just
+/// a frame while intact, one more crack for every hit.
+#[derive(Resource)]
+struct ArmorImages(Vec<Handle<Image>>);
+
+impl ArmorImages {
+    fn new(images: &mut Assets<Image>) -> Self {
+        let mut image = Image::new_fill(
+            Extent3d {
+                width: ARMOR_TEXELS,
+                height: ARMOR_TEXELS,
+                depth_or_array_layers: 1,
             },
-            MeshMaterial2d(materials.add(Color::from(Color::Srgba(OLIVE)))),
-            BluePlayer::new(),
-        ));
+            TextureDimension::D2,
+            &[255; 4],
+            TextureFormat::Rgba8UnormSrgb,
+            RenderAssetUsages::default(),
+        );
+        image.sampler = ImageSampler::nearest();
+        let last = ARMOR_TEXELS - 1;
+        for y in 0..ARMOR_TEXELS {
+            for x in 0..ARMOR_TEXELS {
+                if x < 2 || y < 2 || x > last - 2 || y > last - 2 {
+                    put_texel(&mut image, x, y, ARMOR_FRAME);
+                }
+            }
+        }
+
+        // the same seed every match, so a brick always cracks the same way
+        let mut rng = StdRng::seed_from_u64(0);
+        let handles = (0..ARMOR_HITS)
+            .map(|damage| {
+                if damage > 0 {
+                    draw_crack(&mut image, &mut rng);
+                }
+                images.add(image.clone())
+            })
+            .collect();
+        ArmorImages(handles)
     }
-}
 
-impl BluePlayer {
-    fn new() -> Self {
-        Self { x: -100., y: -100. }
+    fn image(&self, cell: Cell) -> Handle<Image> {
+        match cell {
+            Cell::Armored { hits, .. } => {
+                let damage = ARMOR_HITS.saturating_sub(hits) as usize;
+                self.0[damage.min(self.0.len() - 1)].clone()
+            }
+            _ => Handle::default(),
+        }
     }
 }
 
-impl Plugin for RegionGamePlugin {
-    fn build(&self, app: &mut App) {
-        app.add_systems(
-            OnEnter(GameState::RegionGame),
-            (setup_basedata, setup_player).chain(),
-        )
-        .add_systems(
-            OnExit(GameState::RegionGame),
-            (
-                despawn_with_component::<Brick>,
-                despawn_with_component::<Collider>,
-                despawn_with_component::<PlayBoard>,
-                despawn_with_component::<ReturnButton>,
-                despawn_with_component::<RedPlayer>,
-                despawn_with_component::<BluePlayer>,
-            ),
-        )
-        .add_systems(
-            FixedUpdate,
-            (
-                handle_move::<RedPlayer>,
-                handle_move::<BluePlayer>,
-                check_collider::<RedPlayer>,
-                check_collider::<BluePlayer>,
-                handle_score_update,
-            )
-                .chain()
-                .run_if(in_state(GameState::RegionGame)),
-        )
-        .add_systems(
-            Update,
-            (common_button_system, menu_action)
-                .chain()
-                .run_if(in_state(GameState::RegionGame)),
-        );
+// A jagged line from somewhere near the middle of the brick towards an edge
+fn draw_crack(image: &mut Image, rng: &mut StdRng) {
+    let mut x: i32 = rng.gen_range(6..14);
+    let mut y: i32 = rng.gen_range(6..14);
+    let (dx, dy) = [(1, 1), (1, -1), (-1, 1), (-1, -1)][rng.gen_range(0..4)];
+    let size = ARMOR_TEXELS as i32;
+    while (0..size).contains(&x) && (0..size).contains(&y) {
+        put_texel(image, x as u32, y as u32, ARMOR_CRACK);
+        // mostly diagonal, sometimes straight, so the line zigzags
+        match rng.gen_range(0..3) {
+            0 => x += dx,
+            1 => y += dy,
+            _ => {
+                x += dx;
+                y += dy;
+            }
+        }
     }
 }
 
-fn setup_basedata(mut commands: Commands, asset_server: Res<AssetServer>) {
-    for index_y in 0..BRICK_COUNT_WIDTH + 1 {
-        let real_y = (index_y - MID_POS) * BRICK_WIDTH;
-        for index_x in 0..BRICK_COUNT_WIDTH + 1 {
-            let real_x = (index_x - MID_POS) * BRICK_WIDTH;
-            commands.spawn((
-                Sprite {
-                    color: if real_x > 0 {
-                        BluePlayer::RENDER_COLOR
-                    } else {
-                        RedPlayer::RENDER_COLOR
-                    },
-                    ..default()
-                },
-                Transform {
-                    scale: Vec3 {
-                        x: BRICK_WIDTH as f32,
-                        y: BRICK_WIDTH as f32,
-                        z: 0.,
-                    },
-                    translation: Vec3 {
-                        x: real_x as f32,
-                        y: real_y as f32,
-                        z: 0.,
-                    },
-                    ..default()
-                },
-                Brick(if real_x > 0 {
-                    BrickColor::Blue
-                } else {
-                    BrickColor::Red
-                }),
-            ));
+#[allow(clippy::too_many_arguments)]
+fn setup_basedata(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    backend: Res<BoardBackend>,
+    settings: Res<RegionSettings>,
+    viewer: Option<Res<ReplayViewer>>,
+    resumed: Option<Res<ResumedMatch>>,
+    mut images: ResMut<Assets<Image>>,
+) {
+    let world = match viewer {
+        Some(viewer) => viewer.start(),
+        None => {
+            let (world, recorder) = match resumed {
+                Some(resumed) => {
+                    commands.insert_resource(resumed.chart.clone());
+                    commands.insert_resource(resumed.heat.clone());
+                    (resumed.world.clone(), resumed.recorder.clone())
+                }
+                None => (
+                    RegionWorld::new(&settings),
+                    Recorder::new(&settings, TICK_SECONDS),
+                ),
+            };
+            commands.remove_resource::<ResumedMatch>();
+            commands.insert_resource(MatchRecorder(recorder));
+            commands.insert_resource(MatchHistory(History::new(&world)));
+            world
         }
+    };
+    match *backend {
+        BoardBackend::Sprites => {
+            let armor = ArmorImages::new(&mut images);
+            spawn_brick_sprites(&mut commands, &world, &armor);
+            commands.insert_resource(armor);
+        }
+        BoardBackend::Texture => spawn_board_texture(&mut commands, &mut images, &world),
+    }
+
+    for wall in world.walls() {
+        commands.spawn(WallBundle::new(wall));
+    }
+    for team in TeamId::ALL {
+        place_board(&mut commands, &asset_server, team, &world);
     }
-    commands.spawn(WallBundle::new(WallLocation::Left));
-    commands.spawn(WallBundle::new(WallLocation::Right));
-    commands.spawn(WallBundle::new(WallLocation::Bottom));
-    commands.spawn(WallBundle::new(WallLocation::Top));
-    RedPlayer::place_board(&mut commands, &asset_server);
-    BluePlayer::place_board(&mut commands, &asset_server);
+    commands.insert_resource(RegionSim(world));
     commands
         .spawn((
             BackgroundColor(NORMAL_BUTTON),
@@ -448,114 +682,369 @@ fn setup_basedata(mut commands: Commands, asset_server: Res<AssetServer>) {
 
 fn setup_player(
     mut commands: Commands,
+    sim: Res<RegionSim>,
     mut meshes: ResMut<Assets<Mesh>>,
     mut materials: ResMut<Assets<ColorMaterial>>,
 ) {
-    RedPlayer::place_player(&mut commands, &mut meshes, &mut materials);
-    BluePlayer::place_player(&mut commands, &mut meshes, &mut materials);
+    for (index, ball) in sim.balls().iter().enumerate() {
+        spawn_ball(&mut commands, &mut meshes, &mut materials, index, ball);
+    }
 }
-#[derive(Debug, PartialEq, Eq, Copy, Clone)]
-enum Collision {
-    Left,
-    Right,
-    Top,
-    Bottom,
+
+// The mesh is a unit circle scaled to the radius, so growing balls only change
+// their transform
+fn spawn_ball(
+    commands: &mut Commands,
+    meshes: &mut Assets<Mesh>,
+    materials: &mut Assets<ColorMaterial>,
+    index: usize,
+    ball: &Ball,
+) {
+    commands.spawn((
+        Mesh2d(meshes.add(Circle::new(1.))),
+        Transform {
+            translation: ball.position.extend(1.),
+            scale: Vec3 {
+                x: ball.radius,
+                y: ball.radius,
+                z: 2.,
+            },
+            ..default()
+        },
+        MeshMaterial2d(materials.add(team_style(ball.team).ball_color)),
+        RegionBall(index),
+    ));
 }
 
-// Returns `Some` if `ball` collides with `bounding_box`.
-// The returned `Collision` is the side of `bounding_box` that `ball` hit.
-fn ball_collision(ball: BoundingCircle, bounding_box: Aabb2d) -> Option<Collision> {
-    if !ball.intersects(&bounding_box) {
-        return None;
+// Balls split off by a multi-ball pickup are appended to `RegionWorld::balls`
+fn spawn_split_balls(
+    mut commands: Commands,
+    sim: Res<RegionSim>,
+    balls: Query<(Entity, &RegionBall)>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+) {
+    // jumping back in a replay can also remove balls
+    for (entity, ball) in &balls {
+        if ball.0 >= sim.balls().len() {
+            commands.entity(entity).despawn();
+        }
     }
+    let spawned = balls.iter().count().min(sim.balls().len());
+    for (index, ball) in sim.balls().iter().enumerate().skip(spawned) {
+        spawn_ball(&mut commands, &mut meshes, &mut materials, index, ball);
+    }
+}
 
-    let closest = bounding_box.closest_point(ball.center());
-    let offset: Vec2 = ball.center() - closest;
-    let side = if offset.x.abs() > offset.y.abs() {
-        if offset.x < 0. {
-            Collision::Left
-        } else {
-            Collision::Right
-        }
-    } else if offset.y > 0. {
-        Collision::Top
+fn cleanup_sim(mut commands: Commands) {
+    commands.remove_resource::<RegionSim>();
+    commands.remove_resource::<BrickEntities>();
+    commands.remove_resource::<ArmorImages>();
+}
+
+// Turns the held keys of each player into a steering direction, the simulation
+// ignores it unless the match has steering on
+fn read_steering(
+    mut sim: ResMut<RegionSim>,
+    mut recorder: ResMut<MatchRecorder>,
+    keys: Res<ButtonInput<KeyCode>>,
+) {
+    for binding in &STEERING_KEYS {
+        let axis = |negative, positive| {
+            keys.pressed(positive) as i32 as f32 - keys.pressed(negative) as i32 as f32
+        };
+        let direction = Vec2::new(
+            axis(binding.left, binding.right),
+            axis(binding.down, binding.up),
+        );
+        let input = SteeringInput {
+            direction: direction.normalize_or_zero(),
+            boost: keys.pressed(binding.boost),
+        };
+        recorder.apply(
+            &mut sim,
+            ReplayInput::Steer {
+                team: binding.team,
+                input,
+            },
+        );
+    }
+}
+
+// Left click places a red barrier and right click a blue one, horizontal unless
+// Ctrl is held. The cursor goes through the camera, so clicks land in the right
+// spot whatever the window size.
+fn click_barriers(
+    buttons: Res<ButtonInput<MouseButton>>,
+    keys: Res<ButtonInput<KeyCode>>,
+    windows: Query<&Window, With<PrimaryWindow>>,
+    cameras: Query<(&Camera, &GlobalTransform)>,
+    mut pending: ResMut<PendingBarriers>,
+) {
+    let team = if buttons.just_pressed(MouseButton::Left) {
+        TeamId::RED
+    } else if buttons.just_pressed(MouseButton::Right) {
+        TeamId::BLUE
     } else {
-        Collision::Bottom
+        return;
+    };
+    let Ok(window) = windows.get_single() else {
+        return;
+    };
+    let Ok((camera, camera_transform)) = cameras.get_single() else {
+        return;
+    };
+    let Some(position) = window
+        .cursor_position()
+        .and_then(|cursor| camera.viewport_to_world_2d(camera_transform, cursor).ok())
+    else {
+        return;
     };
+    let vertical = keys.any_pressed([KeyCode::ControlLeft, KeyCode::ControlRight]);
+    pending.0.push((team, position, vertical));
+}
 
-    Some(side)
-}
-
-fn check_collider<P>(
-    mut blocks: Query<(&Transform, &mut Sprite, &mut Brick)>,
-    walls: Query<&Transform, With<Collider>>,
-    mut player: Query<(&Transform, &mut P), With<P>>,
-) where
-    P: Player + Component,
-{
-    let (player_tran, mut player_state) = player.single_mut();
-    for (transform, mut spite, mut block) in &mut blocks {
-        if let Some(coll) = ball_collision(
-            BoundingCircle::new(player_tran.translation.truncate(), 20. / 2.0),
-            Aabb2d::new(
-                transform.translation.truncate(),
-                transform.scale.truncate() / 2.0,
-            ),
-        ) {
-            let x = player_state.x();
-            let y = player_state.y();
-            if block.0 != P::BREAK_COLOR {
-                match coll {
-                    Collision::Left | Collision::Right => player_state.set_x(-x),
-                    Collision::Top | Collision::Bottom => player_state.set_y(-y),
-                }
+fn place_barriers(
+    mut sim: ResMut<RegionSim>,
+    mut recorder: ResMut<MatchRecorder>,
+    mut pending: ResMut<PendingBarriers>,
+) {
+    for (team, center, vertical) in pending.0.drain(..) {
+        recorder.apply(
+            &mut sim,
+            ReplayInput::Barrier {
+                team,
+                center,
+                vertical,
+            },
+        );
+    }
+}
 
-                spite.color = P::RENDER_COLOR;
-                block.0 = P::BREAK_COLOR;
+fn step_sim(mut sim: ResMut<RegionSim>, mut recorder: ResMut<MatchRecorder>) {
+    if sim.outcome().is_some() {
+        return;
+    }
+    // the fixed timestep changes with the sim speed, a tick does not
+    sim.step(TICK_SECONDS);
+    recorder.record_tick(&sim);
+}
+
+// Passes the simulation's events on, for views that react to them rather than
+// compare the world between ticks
+fn send_world_events(
+    mut sim: ResMut<RegionSim>,
+    mut outcome: Local<Option<MatchOutcome>>,
+    mut captured: EventWriter<BrickCaptured>,
+    mut bounced: EventWriter<BallBounced>,
+    mut ended: EventWriter<MatchEnded>,
+) {
+    for event in sim.drain_events() {
+        match event {
+            WorldEvent::Captured {
+                cell,
+                from,
+                to,
+                ball,
+            } => {
+                captured.send(BrickCaptured {
+                    cell,
+                    from,
+                    to,
+                    ball,
+                });
             }
+            WorldEvent::Bounced { ball, surface } => {
+                bounced.send(BallBounced { ball, surface });
+            }
+        }
+    }
+    let current = sim.outcome();
+    if let (None, Some(result)) = (*outcome, current) {
+        ended.send(MatchEnded {
+            winner: match result {
+                MatchOutcome::Winner(team) => Some(team),
+                MatchOutcome::Draw => None,
+            },
+        });
+    }
+    *outcome = current;
+}
+
+fn log_stalemates(mut sim: ResMut<RegionSim>) {
+    for stalemate in sim.drain_stalemates() {
+        info!("{stalemate}");
+    }
+}
+
+fn log_match_events(
+    mut captured: EventReader<BrickCaptured>,
+    mut bounced: EventReader<BallBounced>,
+    mut ended: EventReader<MatchEnded>,
+) {
+    for event in captured.read() {
+        debug!(
+            "ball {} captured cell {}: {:?} -> {:?}",
+            event.ball, event.cell, event.from, event.to
+        );
+    }
+    for event in bounced.read() {
+        debug!("ball {} bounced off {:?}", event.ball, event.surface);
+    }
+    for event in ended.read() {
+        match event.winner {
+            Some(team) => info!("match over, {} wins", team_style(team).name),
+            None => info!("match over, draw"),
         }
     }
-    for transform in &walls {
-        if let Some(coll) = ball_collision(
-            BoundingCircle::new(player_tran.translation.truncate(), 20. / 2.0),
-            Aabb2d::new(
-                transform.translation.truncate(),
-                transform.scale.truncate() / 2.0,
-            ),
-        ) {
-            let x = player_state.x();
-            let y = player_state.y();
-            match coll {
-                Collision::Left | Collision::Right => player_state.set_x(-x),
-                Collision::Top | Collision::Bottom => player_state.set_y(-y),
+}
+
+/// Paints single cells on whichever board backend is active, for views that
+/// show another board than the current one.
+#[derive(SystemParam)]
+pub struct BoardPainter<'w, 's> {
+    bricks: Option<Res<'w, BrickEntities>>,
+    armor: Option<Res<'w, ArmorImages>>,
+    sprites: Query<'w, 's, &'static mut Sprite, With<Brick>>,
+    board: Query<'w, 's, &'static BoardTexture>,
+    images: ResMut<'w, Assets<Image>>,
+}
+
+impl BoardPainter<'_, '_> {
+    pub fn paint(&mut self, grid: Grid, cell: usize, state: Cell) {
+        if let (Some(bricks), Some(armor)) = (&self.bricks, &self.armor) {
+            if let Ok(mut sprite) = self.sprites.get_mut(bricks.0[cell]) {
+                sprite.color = cell_color(state);
+                sprite.image = armor.image(state);
+            }
+        } else if let Ok(BoardTexture(handle)) = self.board.get_single() {
+            if let Some(image) = self.images.get_mut(handle) {
+                paint_texel(image, grid, cell, cell_color(state));
             }
         }
     }
 }
 
-fn handle_move<P>(mut blue_query: Query<(&mut Transform, &P), With<P>>, timer: Res<Time<Fixed>>)
-where
-    P: Player + Component,
-{
-    let (mut player_trans, state) = blue_query.single_mut();
+fn sync_bricks(
+    mut sim: ResMut<RegionSim>,
+    bricks: Res<BrickEntities>,
+    armor: Res<ArmorImages>,
+    mut sprites: Query<&mut Sprite, With<Brick>>,
+) {
+    let captures: Vec<usize> = sim.drain_captures().collect();
+    for cell in captures {
+        if let Ok(mut sprite) = sprites.get_mut(bricks.0[cell]) {
+            let state = sim.cells()[cell];
+            sprite.color = cell_color(state);
+            sprite.image = armor.image(state);
+        }
+    }
+}
+
+fn sync_board_texture(
+    mut sim: ResMut<RegionSim>,
+    board: Query<&BoardTexture>,
+    mut images: ResMut<Assets<Image>>,
+) {
+    let captures: Vec<usize> = sim.drain_captures().collect();
+    // only touch the image when something changed, `get_mut` re-uploads it
+    if captures.is_empty() {
+        return;
+    }
+    let Ok(BoardTexture(handle)) = board.get_single() else {
+        return;
+    };
+    let Some(image) = images.get_mut(handle) else {
+        return;
+    };
+    for cell in captures {
+        paint_texel(image, sim.grid(), cell, cell_color(sim.cells()[cell]));
+    }
+}
 
-    player_trans.translation.x += state.x() * timer.delta().as_secs_f32();
-    player_trans.translation.y += state.y() * timer.delta().as_secs_f32();
+fn sync_balls(sim: Res<RegionSim>, mut balls: Query<(&mut Transform, &RegionBall)>) {
+    for (mut transform, ball) in &mut balls {
+        if let Some(state) = sim.balls().get(ball.0) {
+            transform.translation.x = state.position.x;
+            transform.translation.y = state.position.y;
+            transform.scale.x = state.radius;
+            transform.scale.y = state.radius;
+        }
+    }
 }
 
+#[allow(clippy::type_complexity)]
 fn handle_score_update(
     text_query: Query<(Entity, &PlayerScore), (With<Text>, With<PlayerScore>)>,
-    blocks: Query<&Brick>,
+    added: Query<(), Added<PlayerScore>>,
+    mut captures: EventReader<BrickCaptured>,
+    mut last_tick: Local<Option<u32>>,
+    sim: Res<RegionSim>,
     mut writer: TextUiWriter,
 ) {
+    // a seek in the replay viewer changes the score without any captures
+    let jumped = last_tick.is_none_or(|tick| sim.tick() != tick && sim.tick() != tick + 1);
+    *last_tick = Some(sim.tick());
+    let captured = captures.read().count() > 0;
+    if !captured && !jumped && added.is_empty() {
+        return;
+    }
+    // walls and void are not part of the score, only cells a team can own
+    let capturable = sim.capturable().max(1);
     for (text, playerboard) in &text_query {
-        let count = blocks.iter().filter(|b| b.0 == playerboard.0).count();
-        *writer.text(text, 0) = count.to_string();
+        let count = sim.count(playerboard.0);
+        *writer.text(text, 0) = format!("{count} ({}%)", count * 100 / capturable);
+    }
+}
+
+// Barriers are drawn like the arena walls, tinted with the team that placed them
+fn sync_barriers(
+    mut commands: Commands,
+    sim: Res<RegionSim>,
+    walls: Query<(Entity, &BarrierWall)>,
+) {
+    for (entity, wall) in &walls {
+        if !sim.barriers().iter().any(|barrier| barrier.id == wall.0) {
+            commands.entity(entity).despawn();
+        }
+    }
+    for barrier in sim.barriers() {
+        if walls.iter().any(|(_, wall)| wall.0 == barrier.id) {
+            continue;
+        }
+        let mut wall = WallBundle::new(&barrier.aabb);
+        wall.sprite.color = team_style(barrier.team).ball_color;
+        commands.spawn((wall, BarrierWall(barrier.id)));
+    }
+}
+
+fn update_barrier_hud(sim: Res<RegionSim>, mut huds: Query<(&mut Text, &BarrierHud)>) {
+    for (mut text, hud) in &mut huds {
+        let Some(stock) = sim.barrier_stock(hud.0) else {
+            continue;
+        };
+        **text = if stock.cooldown > 0 {
+            format!(
+                "BARRIERS {} ({:.1}s)",
+                stock.charges,
+                stock.cooldown as f32 * TICK_SECONDS
+            )
+        } else {
+            format!("BARRIERS {}", stock.charges)
+        };
+    }
+}
+
+fn sync_boost_bars(sim: Res<RegionSim>, mut bars: Query<(&mut Node, &BoostBar)>) {
+    for (mut node, bar) in &mut bars {
+        if let Some(meter) = sim.boost_meter(bar.0) {
+            node.width = Val::Percent(meter * 100.);
+        }
     }
 }
 
 fn menu_action(
-    interaction_query: Query<&Interaction, (Changed<Interaction>, With<Button>)>,
+    interaction_query: Query<&Interaction, (Changed<Interaction>, With<ReturnButton>)>,
     mut game_state: ResMut<NextState<GameState>>,
 ) {
     for interaction in &interaction_query {
@@ -564,3 +1053,25 @@ fn menu_action(
         }
     }
 }
+
+fn update_speed_hud(sim: Res<RegionSim>, mut huds: Query<(&mut Text, &SpeedHud)>) {
+    for (mut text, hud) in &mut huds {
+        let title = if sim.is_sudden_death() {
+            "SUDDEN DEATH"
+        } else {
+            "SPEED"
+        };
+        **text = match sim.ticks_left() {
+            Some(ticks) => {
+                let seconds = (ticks as f32 * TICK_SECONDS).ceil() as u32;
+                format!(
+                    "{title} x{:.2}\nTIME {}:{:02}",
+                    sim.speed(hud.0),
+                    seconds / 60,
+                    seconds % 60
+                )
+            }
+            None => format!("{title} x{:.2}", sim.speed(hud.0)),
+        };
+    }
+}
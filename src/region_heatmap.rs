@@ -0,0 +1,218 @@
+// How often each cell changed owner this match, shown over the board as a
+// heatmap while H is toggled on. Hot spots show where a ball keeps looping and
+// which parts of a map get fought over.
+use bevy::{
+    image::ImageSampler,
+    prelude::*,
+    render::{
+        render_asset::RenderAssetUsages,
+        render_resource::{Extent3d, TextureDimension, TextureFormat},
+    },
+};
+
+use crate::{
+    common::{FIRASANS_FONT, GAME_DATA_TEXT_COLOR},
+    region_game::RegionSim,
+    region_sim::{Cell, RegionWorld},
+    utils::despawn_with_component,
+    GameState,
+};
+
+// over the bricks, under the balls
+const HEATMAP_Z: f32 = 0.5;
+// from a single change to the most changes of any cell
+const HEAT_GRADIENT: [Color; 3] = [
+    Color::srgba(0.1, 0.3, 1., 0.5),
+    Color::srgba(1., 0.9, 0.1, 0.7),
+    Color::srgba(1., 0.1, 0.05, 0.85),
+];
+
+pub struct RegionHeatmapPlugin;
+
+impl Plugin for RegionHeatmapPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(OnEnter(GameState::RegionGame), reset_heat)
+            .add_systems(
+                OnExit(GameState::RegionGame),
+                (cleanup_heat, despawn_with_component::<OnHeatmap>),
+            )
+            .add_systems(
+                Update,
+                (toggle_heatmap, paint_heatmap)
+                    .chain()
+                    .run_if(in_state(GameState::RegionGame))
+                    .run_if(resource_exists::<RegionSim>),
+            );
+    }
+}
+
+/// Owner changes of every cell this match.
+#[derive(Resource, Default, Clone)]
+pub struct CaptureHeat {
+    counts: Vec<u32>,
+    most: u32,
+    // the board as of `tick`, to tell owner changes from other captures
+    board: Vec<Cell>,
+    tick: u32,
+}
+
+impl CaptureHeat {
+    /// Counts the owner changes of the tick `world` just played, `true` when
+    /// the heatmap has to be repainted.
+    pub fn count(&mut self, world: &RegionWorld) -> bool {
+        // a new board, or the replay viewer went back
+        if self.board.len() != world.cells().len() || world.tick() < self.tick {
+            *self = CaptureHeat {
+                counts: vec![0; world.cells().len()],
+                most: 0,
+                board: world.cells().to_vec(),
+                tick: world.tick(),
+            };
+            return true;
+        }
+        let mut owners_changed = false;
+        self.tick = world.tick();
+        for &cell in world.captures() {
+            let after = world.cells()[cell];
+            let before = std::mem::replace(&mut self.board[cell], after);
+            if before.owner() != after.owner() {
+                self.counts[cell] += 1;
+                self.most = self.most.max(self.counts[cell]);
+                owners_changed = true;
+            }
+        }
+        owners_changed
+    }
+}
+
+#[derive(Component)]
+struct OnHeatmap;
+
+#[derive(Component)]
+struct HeatmapImage(Handle<Image>);
+
+#[derive(Component)]
+struct HeatmapLegend;
+
+// keeps the heat of a resumed match, which `setup_basedata` puts in
+fn reset_heat(mut commands: Commands) {
+    commands.init_resource::<CaptureHeat>();
+}
+
+fn cleanup_heat(mut commands: Commands) {
+    commands.remove_resource::<CaptureHeat>();
+}
+
+/// Counts the owner changes of the tick that was just played. Has to run before
+/// the captures are drained.
+pub fn count_captures(sim: Res<RegionSim>, mut heat: ResMut<CaptureHeat>) {
+    // only owner changes repaint the heatmap, not every tick that was played
+    if heat.bypass_change_detection().count(&sim) {
+        heat.set_changed();
+    }
+}
+
+fn toggle_heatmap(
+    mut commands: Commands,
+    keys: Res<ButtonInput<KeyCode>>,
+    asset_server: Res<AssetServer>,
+    sim: Res<RegionSim>,
+    mut images: ResMut<Assets<Image>>,
+    heatmaps: Query<Entity, With<OnHeatmap>>,
+) {
+    if !keys.just_pressed(KeyCode::KeyH) {
+        return;
+    }
+    if !heatmaps.is_empty() {
+        for entity in &heatmaps {
+            commands.entity(entity).despawn_recursive();
+        }
+        return;
+    }
+
+    let grid = sim.grid();
+    let mut image = Image::new_fill(
+        Extent3d {
+            width: grid.columns as u32,
+            height: grid.rows as u32,
+            depth_or_array_layers: 1,
+        },
+        TextureDimension::D2,
+        &[0, 0, 0, 0],
+        TextureFormat::Rgba8UnormSrgb,
+        RenderAssetUsages::default(),
+    );
+    image.sampler = ImageSampler::nearest();
+    let handle = images.add(image);
+    commands.spawn((
+        Sprite {
+            image: handle.clone(),
+            custom_size: Some(Vec2::new(grid.columns as f32, grid.rows as f32) * grid.brick_size),
+            ..default()
+        },
+        Transform::from_xyz(0., 0., HEATMAP_Z),
+        HeatmapImage(handle),
+        OnHeatmap,
+    ));
+    commands.spawn((
+        Text::default(),
+        TextFont {
+            font: asset_server.load(FIRASANS_FONT),
+            font_size: 20.0,
+            ..default()
+        },
+        TextColor(GAME_DATA_TEXT_COLOR),
+        Node {
+            position_type: PositionType::Absolute,
+            top: Val::Px(40.0),
+            right: Val::Px(10.),
+            ..default()
+        },
+        HeatmapLegend,
+        OnHeatmap,
+    ));
+}
+
+fn paint_heatmap(
+    heat: Res<CaptureHeat>,
+    heatmaps: Query<Ref<HeatmapImage>>,
+    mut legends: Query<&mut Text, With<HeatmapLegend>>,
+    mut images: ResMut<Assets<Image>>,
+    sim: Res<RegionSim>,
+) {
+    for heatmap in &heatmaps {
+        if !heat.is_changed() && !heatmap.is_added() {
+            continue;
+        }
+        let Some(image) = images.get_mut(&heatmap.0) else {
+            continue;
+        };
+        let grid = sim.grid();
+        for (cell, count) in heat.counts.iter().enumerate() {
+            let (column, row) = grid.coords(cell);
+            // image rows run top to bottom, grid rows bottom to top
+            let texel = ((grid.rows - 1 - row) * grid.columns + column) * 4;
+            image.data[texel..texel + 4].copy_from_slice(&heat_color(*count, heat.most));
+        }
+        for mut text in &mut legends {
+            **text = format!("HEATMAP most changes {}", heat.most);
+        }
+    }
+}
+
+fn heat_color(count: u32, most: u32) -> [u8; 4] {
+    if count == 0 {
+        return [0; 4];
+    }
+    // one change is the cold end, the busiest cell the hot end
+    let heat = if most > 1 {
+        (count - 1) as f32 / (most - 1) as f32 * (HEAT_GRADIENT.len() - 1) as f32
+    } else {
+        0.
+    };
+    let index = (heat as usize).min(HEAT_GRADIENT.len() - 2);
+    HEAT_GRADIENT[index]
+        .mix(&HEAT_GRADIENT[index + 1], heat - index as f32)
+        .to_srgba()
+        .to_u8_array()
+}
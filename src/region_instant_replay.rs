@@ -0,0 +1,182 @@
+// Instant replays of Region Battle matches. The last seconds of the live match
+// are kept in a `History`; pressing R, or a big enough swing in territory,
+// pauses the match and plays that window again in slow motion.
+use bevy::prelude::*;
+
+use crate::{
+    common::{FIRASANS_FONT, GAME_DATA_TEXT_COLOR},
+    pause::PauseState,
+    region_game::{BoardPainter, RegionBall, RegionMode, RegionSim},
+    region_sim::History,
+    utils::despawn_with_component,
+    GameState,
+};
+
+// ticks played per fixed update
+const REPLAY_SPEED: f32 = 0.25;
+// share of the capturable cells a team has to win or lose to start a replay
+const AUTO_SWING: f32 = 0.02;
+
+pub struct RegionInstantReplayPlugin;
+
+impl Plugin for RegionInstantReplayPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            OnExit(GameState::RegionGame),
+            (
+                cleanup_instant_replay,
+                despawn_with_component::<OnInstantReplay>,
+            ),
+        )
+        .add_systems(
+            Update,
+            start_instant_replay
+                .run_if(in_state(RegionMode::Live))
+                .run_if(in_state(PauseState::Running))
+                .run_if(resource_exists::<MatchHistory>)
+                .run_if(not(resource_exists::<InstantReplay>)),
+        )
+        .add_systems(
+            FixedUpdate,
+            play_instant_replay.run_if(resource_exists::<InstantReplay>),
+        );
+    }
+}
+
+/// The last seconds of the live match.
+#[derive(Resource, Deref, DerefMut)]
+pub struct MatchHistory(pub History);
+
+/// An instant replay in progress. The match is paused while it exists.
+#[derive(Resource)]
+pub struct InstantReplay {
+    // tick on screen
+    tick: u32,
+    // tick the match is paused at
+    end: u32,
+    // ticks owed at speeds that do not play a whole number per fixed update
+    progress: f32,
+}
+
+#[derive(Component)]
+struct OnInstantReplay;
+
+/// Adds the tick `step_sim` just played to the history.
+pub fn record_history(sim: Res<RegionSim>, mut history: ResMut<MatchHistory>) {
+    // the match stops stepping once it is over
+    if sim.tick() != history.last_tick() {
+        history.record(&sim);
+    }
+}
+
+fn start_instant_replay(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    keys: Res<ButtonInput<KeyCode>>,
+    sim: Res<RegionSim>,
+    history: Res<MatchHistory>,
+    mut painter: BoardPainter,
+    mut balls: Query<(&mut Transform, &mut Visibility, &RegionBall)>,
+) {
+    if sim.outcome().is_some() || history.first_tick() == history.last_tick() {
+        return;
+    }
+    let swing = history.swing() as f32 / sim.capturable().max(1) as f32;
+    if !keys.just_pressed(KeyCode::KeyR) && swing < AUTO_SWING {
+        return;
+    }
+
+    // rewind the board to the oldest tick
+    for change in history.changes().rev() {
+        painter.paint(sim.grid(), change.cell, change.before);
+    }
+    let replay = InstantReplay {
+        tick: history.first_tick(),
+        end: history.last_tick(),
+        progress: 0.,
+    };
+    show_balls(&history, replay.tick, &mut balls);
+    commands.insert_resource(replay);
+
+    commands
+        .spawn((
+            Node {
+                position_type: PositionType::Absolute,
+                top: Val::Px(60.0),
+                width: Val::Percent(100.0),
+                justify_content: JustifyContent::Center,
+                ..default()
+            },
+            OnInstantReplay,
+        ))
+        .with_child((
+            Text::new("INSTANT REPLAY"),
+            TextFont {
+                font: asset_server.load(FIRASANS_FONT),
+                font_size: 50.0,
+                ..default()
+            },
+            TextColor(GAME_DATA_TEXT_COLOR),
+        ));
+}
+
+fn play_instant_replay(
+    mut commands: Commands,
+    mut replay: ResMut<InstantReplay>,
+    mut sim: ResMut<RegionSim>,
+    mut history: ResMut<MatchHistory>,
+    mut painter: BoardPainter,
+    mut balls: Query<(&mut Transform, &mut Visibility, &RegionBall)>,
+    banners: Query<Entity, With<OnInstantReplay>>,
+) {
+    replay.progress += REPLAY_SPEED;
+    while replay.progress >= 1. && replay.tick < replay.end {
+        replay.progress -= 1.;
+        replay.tick += 1;
+        let tick = replay.tick;
+        for change in history.changes().filter(|change| change.tick == tick) {
+            painter.paint(sim.grid(), change.cell, change.after);
+        }
+    }
+    show_balls(&history, replay.tick, &mut balls);
+    if replay.tick < replay.end {
+        return;
+    }
+
+    // back to the match, which has not moved on in the meantime
+    commands.remove_resource::<InstantReplay>();
+    sim.touch_all_cells();
+    for (_, mut visibility, _) in &mut balls {
+        *visibility = Visibility::Inherited;
+    }
+    // the swing that started this replay should not start another one
+    history.clear();
+    for banner in &banners {
+        commands.entity(banner).despawn_recursive();
+    }
+}
+
+// Balls that did not exist yet at `tick` are hidden
+fn show_balls(
+    history: &History,
+    tick: u32,
+    balls: &mut Query<(&mut Transform, &mut Visibility, &RegionBall)>,
+) {
+    for (mut transform, mut visibility, ball) in balls {
+        match history.balls_at(tick).nth(ball.0) {
+            Some(sample) => {
+                transform.translation.x = sample.position.x;
+                transform.translation.y = sample.position.y;
+                transform.scale.x = sample.radius;
+                transform.scale.y = sample.radius;
+                *visibility = Visibility::Inherited;
+            }
+            None => *visibility = Visibility::Hidden,
+        }
+    }
+}
+
+fn cleanup_instant_replay(mut commands: Commands) {
+    commands.remove_resource::<InstantReplay>();
+    commands.remove_resource::<MatchHistory>();
+}
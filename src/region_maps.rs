@@ -0,0 +1,85 @@
+use bevy::{
+    asset::{io::Reader, AssetLoader, LoadContext, LoadedFolder},
+    prelude::*,
+};
+
+use crate::region_sim::{MapError, RegionMap};
+
+// Every `.map` file in here shows up in the level select list
+const MAPS_FOLDER: &str = "maps";
+
+pub struct RegionMapsPlugin;
+
+impl Plugin for RegionMapsPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_asset::<RegionMapAsset>()
+            .init_asset_loader::<RegionMapLoader>()
+            .add_systems(Startup, load_maps);
+    }
+}
+
+/// A Region Battle arena loaded from `assets/maps/`.
+#[derive(Asset, TypePath, Debug, Deref)]
+pub struct RegionMapAsset(pub RegionMap);
+
+/// The `assets/maps/` folder, loaded at startup for the level select list.
+#[derive(Resource)]
+pub struct MapFolder(pub Handle<LoadedFolder>);
+
+#[derive(Debug)]
+pub enum MapLoadError {
+    Io(std::io::Error),
+    Utf8(std::str::Utf8Error),
+    Parse(MapError),
+}
+
+impl std::fmt::Display for MapLoadError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MapLoadError::Io(error) => write!(f, "could not read map: {error}"),
+            MapLoadError::Utf8(error) => write!(f, "map is not valid UTF-8: {error}"),
+            MapLoadError::Parse(error) => write!(f, "{error}"),
+        }
+    }
+}
+
+impl std::error::Error for MapLoadError {}
+
+#[derive(Default)]
+struct RegionMapLoader;
+
+impl AssetLoader for RegionMapLoader {
+    type Asset = RegionMapAsset;
+    type Settings = ();
+    type Error = MapLoadError;
+
+    async fn load(
+        &self,
+        reader: &mut dyn Reader,
+        _settings: &(),
+        load_context: &mut LoadContext<'_>,
+    ) -> Result<Self::Asset, Self::Error> {
+        let mut bytes = Vec::new();
+        reader
+            .read_to_end(&mut bytes)
+            .await
+            .map_err(MapLoadError::Io)?;
+        let source = std::str::from_utf8(&bytes).map_err(MapLoadError::Utf8)?;
+        let fallback_name = load_context
+            .path()
+            .file_stem()
+            .and_then(|stem| stem.to_str())
+            .unwrap_or("unnamed");
+        RegionMap::parse(source, fallback_name)
+            .map(RegionMapAsset)
+            .map_err(MapLoadError::Parse)
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["map"]
+    }
+}
+
+fn load_maps(mut commands: Commands, asset_server: Res<AssetServer>) {
+    commands.insert_resource(MapFolder(asset_server.load_folder(MAPS_FOLDER)));
+}
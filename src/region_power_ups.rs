@@ -0,0 +1,171 @@
+use bevy::{
+    color::palettes::css::{AQUA, FUCHSIA, GOLD, LIME, ORANGE},
+    prelude::*,
+};
+
+use crate::{
+    common::FIRASANS_FONT,
+    region_game::RegionSim,
+    region_sim::{PowerUpKind, TeamId, PICKUP_RADIUS, TICK_SECONDS},
+    utils::despawn_with_component,
+    GameState,
+};
+
+const FLASH_SECONDS: f32 = 0.4;
+const FLASH_GROWTH: f32 = 3.;
+
+pub struct RegionPowerUpsPlugin;
+
+impl Plugin for RegionPowerUpsPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            OnExit(GameState::RegionGame),
+            (
+                despawn_with_component::<PickupSprite>,
+                despawn_with_component::<PickupFlash>,
+            ),
+        )
+        .add_systems(
+            Update,
+            (
+                sync_pickups,
+                spawn_pickup_flashes,
+                animate_pickup_flashes,
+                update_power_up_hud,
+            )
+                .run_if(in_state(GameState::RegionGame))
+                .run_if(resource_exists::<RegionSim>),
+        );
+    }
+}
+
+/// Lists the running power-ups of a team with their remaining time.
+#[derive(Component)]
+pub struct PowerUpHud(pub TeamId);
+
+/// Mirrors the pickup with the same id in `RegionWorld::pickups`.
+#[derive(Component)]
+struct PickupSprite(u32);
+
+#[derive(Component)]
+struct PickupFlash(Timer);
+
+pub fn power_up_title(kind: PowerUpKind) -> &'static str {
+    match kind {
+        PowerUpKind::SpeedBoost => "Speed",
+        PowerUpKind::Growth => "Growth",
+        PowerUpKind::MultiBall => "Multi-Ball",
+        PowerUpKind::Shield => "Shield",
+        PowerUpKind::PaintBomb => "Paint Bomb",
+    }
+}
+
+fn power_up_color(kind: PowerUpKind) -> Color {
+    match kind {
+        PowerUpKind::SpeedBoost => GOLD.into(),
+        PowerUpKind::Growth => LIME.into(),
+        PowerUpKind::MultiBall => ORANGE.into(),
+        PowerUpKind::Shield => AQUA.into(),
+        PowerUpKind::PaintBomb => FUCHSIA.into(),
+    }
+}
+
+// Pickups are drawn as diamonds, the same shape the pickup flash expands from
+fn diamond(kind: PowerUpKind, position: Vec2) -> (Sprite, Transform) {
+    (
+        Sprite {
+            color: power_up_color(kind),
+            custom_size: Some(Vec2::splat(PICKUP_RADIUS * 1.5)),
+            ..default()
+        },
+        Transform {
+            translation: position.extend(0.5),
+            rotation: Quat::from_rotation_z(std::f32::consts::FRAC_PI_4),
+            ..default()
+        },
+    )
+}
+
+fn sync_pickups(
+    mut commands: Commands,
+    sim: Res<RegionSim>,
+    sprites: Query<(Entity, &PickupSprite)>,
+    asset_server: Res<AssetServer>,
+) {
+    for (entity, sprite) in &sprites {
+        if !sim.pickups().iter().any(|pickup| pickup.id == sprite.0) {
+            commands.entity(entity).despawn_recursive();
+        }
+    }
+    for pickup in sim.pickups() {
+        if sprites.iter().any(|(_, sprite)| sprite.0 == pickup.id) {
+            continue;
+        }
+        let letter = &power_up_title(pickup.kind)[..1];
+        commands
+            .spawn((
+                diamond(pickup.kind, pickup.position),
+                PickupSprite(pickup.id),
+            ))
+            .with_child((
+                Text2d::new(letter),
+                TextFont {
+                    font: asset_server.load(FIRASANS_FONT),
+                    font_size: 12.0,
+                    ..default()
+                },
+                TextColor(Color::BLACK),
+                // undo the diamond's rotation so the letter stays upright
+                Transform {
+                    translation: Vec3::Z * 0.1,
+                    rotation: Quat::from_rotation_z(-std::f32::consts::FRAC_PI_4),
+                    ..default()
+                },
+            ));
+    }
+}
+
+fn spawn_pickup_flashes(mut commands: Commands, mut sim: ResMut<RegionSim>) {
+    for picked_up in sim.drain_picked_up() {
+        commands.spawn((
+            diamond(picked_up.kind, picked_up.position),
+            PickupFlash(Timer::from_seconds(FLASH_SECONDS, TimerMode::Once)),
+        ));
+    }
+}
+
+// Grows and fades the flash until its timer runs out
+fn animate_pickup_flashes(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut flashes: Query<(Entity, &mut PickupFlash, &mut Sprite, &mut Transform)>,
+) {
+    for (entity, mut flash, mut sprite, mut transform) in &mut flashes {
+        flash.0.tick(time.delta());
+        if flash.0.finished() {
+            commands.entity(entity).despawn();
+            continue;
+        }
+        let progress = flash.0.fraction();
+        transform.scale = Vec3::splat(1. + progress * (FLASH_GROWTH - 1.));
+        sprite.color.set_alpha(1. - progress);
+    }
+}
+
+fn update_power_up_hud(sim: Res<RegionSim>, mut huds: Query<(&mut Text, &PowerUpHud)>) {
+    for (mut text, hud) in &mut huds {
+        let lines: Vec<String> = sim
+            .effects()
+            .iter()
+            .filter(|effect| effect.team == hud.0)
+            .map(|effect| {
+                format!(
+                    "{} {:.1}s",
+                    power_up_title(effect.kind).to_uppercase(),
+                    effect.ticks_left as f32 * TICK_SECONDS
+                )
+            })
+            .collect();
+        **text = lines.join("\n");
+    }
+}
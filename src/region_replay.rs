@@ -0,0 +1,416 @@
+// Recording and playback of Region Battle matches. Every live match is recorded
+// and saved to `REPLAY_DIR` when its screen closes, where the newest
+// `MAX_REPLAYS` are kept. The viewer plays the replay picked on the setup screen
+// in the regular match screen, with `RegionMode::Replay` swapping the player controls for playback
+// controls.
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use bevy::{prelude::*, ui::RelativeCursorPosition};
+
+use crate::{
+    common::{FIRASANS_FONT, GAME_DATA_TEXT_COLOR, NORMAL_BUTTON, TEXT_COLOR},
+    pause::PauseState,
+    region_game::{RegionMode, RegionSim},
+    region_sim::{Playback, Recorder, RegionWorld, Replay},
+    utils::despawn_with_component,
+    GameState,
+};
+
+const REPLAY_DIR: &str = "replays";
+const REPLAY_EXTENSION: &str = "replay";
+// older replays are deleted once there are more than this, few enough to pick
+// from by cycling through them
+const MAX_REPLAYS: usize = 20;
+const PLAYBACK_SPEEDS: [f32; 6] = [0.25, 0.5, 1., 2., 4., 8.];
+const NORMAL_SPEED: usize = 2;
+const SCRUB_BAR_WIDTH: f32 = 600.;
+const SCRUB_BAR_BACKGROUND: Color = Color::srgb(0.25, 0.25, 0.25);
+
+pub struct RegionReplayPlugin;
+
+impl Plugin for RegionReplayPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            OnExit(GameState::RegionGame),
+            (save_recording, cleanup_viewer),
+        )
+        .add_systems(OnEnter(RegionMode::Replay), setup_controls)
+        .add_systems(
+            OnExit(RegionMode::Replay),
+            despawn_with_component::<OnReplayControls>,
+        )
+        .add_systems(
+            Update,
+            (replay_keys, replay_button, scrub, update_controls)
+                .chain()
+                .run_if(in_state(RegionMode::Replay))
+                .run_if(in_state(PauseState::Running))
+                .run_if(resource_exists::<RegionSim>),
+        );
+    }
+}
+
+/// Records the live match.
+#[derive(Resource, Deref, DerefMut)]
+pub struct MatchRecorder(pub Recorder);
+
+/// Playback state of the replay viewer.
+#[derive(Resource)]
+pub struct ReplayViewer {
+    playback: Playback,
+    playing: bool,
+    // index into `PLAYBACK_SPEEDS`
+    speed: usize,
+    // ticks owed at speeds that do not play a whole number per fixed update
+    progress: f32,
+    // why playback stopped early
+    error: Option<String>,
+}
+
+impl ReplayViewer {
+    pub fn new(replay: Replay) -> Self {
+        Self {
+            playback: Playback::new(replay),
+            playing: true,
+            speed: NORMAL_SPEED,
+            progress: 0.,
+            error: None,
+        }
+    }
+
+    /// The world the replay starts from.
+    pub fn start(&self) -> RegionWorld {
+        self.playback.start()
+    }
+
+    fn step(&mut self, world: &mut RegionWorld) {
+        if let Err(error) = self.playback.step(world) {
+            self.fail(error.to_string());
+        }
+        if world.tick() >= self.playback.replay().ticks() {
+            self.playing = false;
+        }
+    }
+
+    fn seek(&mut self, world: &mut RegionWorld, tick: u32) {
+        if let Err(error) = self.playback.seek(world, tick) {
+            self.fail(error.to_string());
+        }
+        self.progress = 0.;
+    }
+
+    fn fail(&mut self, error: String) {
+        warn!("{error}");
+        self.error = Some(error);
+        self.playing = false;
+    }
+}
+
+#[derive(Component)]
+struct OnReplayControls;
+
+#[derive(Component, Clone, Copy)]
+enum ReplayButton {
+    StepBack,
+    PlayPause,
+    StepForward,
+    Slower,
+    Faster,
+}
+
+#[derive(Component)]
+struct ScrubBar;
+
+#[derive(Component)]
+struct ScrubFill;
+
+#[derive(Component)]
+struct ReplayStatus;
+
+/// Replay files, oldest first: they are named after the time they were saved.
+pub fn saved_replays() -> Vec<PathBuf> {
+    let Ok(entries) = fs::read_dir(REPLAY_DIR) else {
+        return Vec::new();
+    };
+    let mut paths: Vec<PathBuf> = entries
+        .filter_map(|entry| Some(entry.ok()?.path()))
+        .filter(|path| path.extension().is_some_and(|ext| ext == REPLAY_EXTENSION))
+        .collect();
+    paths.sort();
+    paths
+}
+
+/// How long ago the replay at `path` was saved, e.g. "5 min ago".
+pub fn replay_age(path: &Path) -> String {
+    let saved = path
+        .file_stem()
+        .and_then(|stem| stem.to_str()?.split('-').next()?.parse::<u64>().ok());
+    let Some(saved) = saved else {
+        return path.display().to_string();
+    };
+    let minutes = unix_seconds().saturating_sub(saved) / 60;
+    match minutes {
+        0 => "just now".to_string(),
+        1..60 => format!("{minutes} min ago"),
+        60..1440 => format!("{} h ago", minutes / 60),
+        _ => format!("{} days ago", minutes / 1440),
+    }
+}
+
+pub fn load_replay(path: &Path) -> Result<Replay, String> {
+    let bytes = fs::read(path).map_err(|error| format!("{}: {error}", path.display()))?;
+    Replay::from_bytes(&bytes).map_err(|error| format!("{}: {error}", path.display()))
+}
+
+fn save_recording(mut commands: Commands, recorder: Option<Res<MatchRecorder>>) {
+    let Some(recorder) = recorder else {
+        return;
+    };
+    commands.remove_resource::<MatchRecorder>();
+    if recorder.replay().ticks() == 0 {
+        return;
+    }
+    let seconds = unix_seconds();
+    // numbered, so matches ending in the same second keep their own file
+    let mut number = 0;
+    let path = loop {
+        let path =
+            PathBuf::from(REPLAY_DIR).join(format!("{seconds:012}-{number:03}.{REPLAY_EXTENSION}"));
+        if !path.exists() {
+            break path;
+        }
+        number += 1;
+    };
+    let result =
+        fs::create_dir_all(REPLAY_DIR).and_then(|_| fs::write(&path, recorder.replay().to_bytes()));
+    match result {
+        Ok(()) => info!("saved replay to {}", path.display()),
+        Err(error) => warn!("could not save replay to {}: {error}", path.display()),
+    }
+    let replays = saved_replays();
+    for old in &replays[..replays.len().saturating_sub(MAX_REPLAYS)] {
+        if let Err(error) = fs::remove_file(old) {
+            warn!("could not delete old replay {}: {error}", old.display());
+        }
+    }
+}
+
+fn unix_seconds() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |time| time.as_secs())
+}
+
+fn cleanup_viewer(mut commands: Commands) {
+    commands.remove_resource::<ReplayViewer>();
+}
+
+/// Plays the replay at the chosen speed, in place of `step_sim`.
+pub fn step_replay(mut sim: ResMut<RegionSim>, mut viewer: ResMut<ReplayViewer>) {
+    if !viewer.playing {
+        return;
+    }
+    viewer.progress += PLAYBACK_SPEEDS[viewer.speed];
+    while viewer.progress >= 1. && viewer.playing {
+        viewer.progress -= 1.;
+        viewer.step(&mut sim);
+    }
+}
+
+fn setup_controls(mut commands: Commands, asset_server: Res<AssetServer>) {
+    let font = asset_server.load(FIRASANS_FONT);
+    commands
+        .spawn((
+            Node {
+                position_type: PositionType::Absolute,
+                bottom: Val::Px(10.0),
+                width: Val::Percent(100.0),
+                flex_direction: FlexDirection::Column,
+                align_items: AlignItems::Center,
+                ..default()
+            },
+            OnReplayControls,
+        ))
+        .with_children(|parent| {
+            parent.spawn((
+                Text::default(),
+                TextFont {
+                    font: font.clone(),
+                    font_size: 20.0,
+                    ..default()
+                },
+                TextColor(GAME_DATA_TEXT_COLOR),
+                ReplayStatus,
+            ));
+            parent
+                .spawn((
+                    Node {
+                        width: Val::Px(SCRUB_BAR_WIDTH),
+                        height: Val::Px(16.0),
+                        margin: UiRect::all(Val::Px(6.0)),
+                        ..default()
+                    },
+                    BackgroundColor(SCRUB_BAR_BACKGROUND),
+                    Interaction::default(),
+                    RelativeCursorPosition::default(),
+                    ScrubBar,
+                ))
+                .with_child((
+                    Node {
+                        width: Val::Percent(0.0),
+                        height: Val::Percent(100.0),
+                        ..default()
+                    },
+                    BackgroundColor(GAME_DATA_TEXT_COLOR),
+                    ScrubFill,
+                ));
+            parent.spawn(Node::default()).with_children(|parent| {
+                for (button, title) in [
+                    (ReplayButton::Slower, "Slower"),
+                    (ReplayButton::StepBack, "<"),
+                    (ReplayButton::PlayPause, "Pause"),
+                    (ReplayButton::StepForward, ">"),
+                    (ReplayButton::Faster, "Faster"),
+                ] {
+                    parent
+                        .spawn((
+                            Button,
+                            Node {
+                                width: Val::Px(100.0),
+                                height: Val::Px(40.0),
+                                margin: UiRect::horizontal(Val::Px(5.0)),
+                                justify_content: JustifyContent::Center,
+                                align_items: AlignItems::Center,
+                                ..default()
+                            },
+                            BackgroundColor(NORMAL_BUTTON),
+                            button,
+                        ))
+                        .with_child((
+                            Text::new(title),
+                            TextFont {
+                                font: font.clone(),
+                                font_size: 20.0,
+                                ..default()
+                            },
+                            TextColor(TEXT_COLOR),
+                        ));
+                }
+            });
+        });
+}
+
+fn control(button: ReplayButton, viewer: &mut ReplayViewer, world: &mut RegionWorld) {
+    match button {
+        ReplayButton::PlayPause => {
+            // playing from the end starts over
+            if !viewer.playing && world.tick() >= viewer.playback.replay().ticks() {
+                viewer.seek(world, 0);
+            }
+            viewer.playing = !viewer.playing && viewer.error.is_none();
+        }
+        ReplayButton::StepBack => {
+            viewer.playing = false;
+            let tick = world.tick().saturating_sub(1);
+            viewer.seek(world, tick);
+        }
+        ReplayButton::StepForward => {
+            viewer.playing = false;
+            viewer.step(world);
+        }
+        ReplayButton::Slower => viewer.speed = viewer.speed.saturating_sub(1),
+        ReplayButton::Faster => viewer.speed = (viewer.speed + 1).min(PLAYBACK_SPEEDS.len() - 1),
+    }
+}
+
+// Space plays and pauses, the arrow keys step a tick and change the speed
+fn replay_keys(
+    keys: Res<ButtonInput<KeyCode>>,
+    mut sim: ResMut<RegionSim>,
+    mut viewer: ResMut<ReplayViewer>,
+) {
+    for (key, button) in [
+        (KeyCode::Space, ReplayButton::PlayPause),
+        (KeyCode::ArrowLeft, ReplayButton::StepBack),
+        (KeyCode::ArrowRight, ReplayButton::StepForward),
+        (KeyCode::ArrowDown, ReplayButton::Slower),
+        (KeyCode::ArrowUp, ReplayButton::Faster),
+    ] {
+        if keys.just_pressed(key) {
+            control(button, &mut viewer, &mut sim);
+        }
+    }
+}
+
+fn replay_button(
+    interaction_query: Query<(&Interaction, &ReplayButton), Changed<Interaction>>,
+    mut sim: ResMut<RegionSim>,
+    mut viewer: ResMut<ReplayViewer>,
+) {
+    for (interaction, button) in &interaction_query {
+        if *interaction == Interaction::Pressed {
+            control(*button, &mut viewer, &mut sim);
+        }
+    }
+}
+
+// Jumps to the tick under the cursor for as long as the bar is held
+fn scrub(
+    bars: Query<(&Interaction, &RelativeCursorPosition), With<ScrubBar>>,
+    mut sim: ResMut<RegionSim>,
+    mut viewer: ResMut<ReplayViewer>,
+) {
+    for (interaction, cursor) in &bars {
+        let Some(position) = cursor.normalized else {
+            continue;
+        };
+        if *interaction != Interaction::Pressed || viewer.error.is_some() {
+            continue;
+        }
+        let ticks = viewer.playback.replay().ticks();
+        let tick = (position.x.clamp(0., 1.) * ticks as f32).round() as u32;
+        if tick != sim.tick() {
+            viewer.seek(&mut sim, tick);
+        }
+    }
+}
+
+fn update_controls(
+    sim: Res<RegionSim>,
+    viewer: Res<ReplayViewer>,
+    mut status: Query<&mut Text, With<ReplayStatus>>,
+    mut fills: Query<&mut Node, With<ScrubFill>>,
+    mut buttons: Query<(&ReplayButton, &Children)>,
+    mut texts: Query<&mut Text, Without<ReplayStatus>>,
+) {
+    let replay = viewer.playback.replay();
+    let seconds = |ticks: u32| (ticks as f32 * replay.tick_seconds()) as u32;
+    let clock = |ticks: u32| format!("{}:{:02}", seconds(ticks) / 60, seconds(ticks) % 60);
+    for mut text in &mut status {
+        **text = match &viewer.error {
+            Some(error) => error.clone(),
+            None => format!(
+                "REPLAY {} / {}  x{}  tick {}",
+                clock(sim.tick()),
+                clock(replay.ticks()),
+                PLAYBACK_SPEEDS[viewer.speed],
+                sim.tick()
+            ),
+        };
+    }
+    let progress = sim.tick() as f32 / replay.ticks().max(1) as f32;
+    for mut node in &mut fills {
+        node.width = Val::Percent(progress * 100.);
+    }
+    for (button, children) in &mut buttons {
+        if let ReplayButton::PlayPause = button {
+            if let Ok(mut text) = texts.get_mut(children[0]) {
+                **text = if viewer.playing { "Pause" } else { "Play" }.to_string();
+            }
+        }
+    }
+}
@@ -0,0 +1,142 @@
+// Saving a live Region Battle match to finish it later. "Save & Quit" writes the
+// match to `SAVE_PATH` and leaves it, or pauses it with the error when the file
+// could not be written. "Continue" on the setup screen loads it back into the
+// match screen.
+use std::{fs, path::Path};
+
+use bevy::prelude::*;
+
+use crate::{
+    common::{FIRASANS_FONT, NORMAL_BUTTON, TEXT_COLOR},
+    pause::{PauseMessage, PauseState},
+    region_chart::TerritoryChart,
+    region_game::{RegionMode, RegionSim},
+    region_heatmap::CaptureHeat,
+    region_replay::MatchRecorder,
+    region_sim::{Recorder, RegionWorld, SavedMatch},
+    utils::despawn_with_component,
+    GameState,
+};
+
+const SAVE_DIR: &str = "saves";
+const SAVE_PATH: &str = "saves/region_battle.save";
+
+pub struct RegionSavePlugin;
+
+impl Plugin for RegionSavePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(OnEnter(RegionMode::Live), setup_save_button)
+            .add_systems(
+                OnExit(RegionMode::Live),
+                despawn_with_component::<SaveButton>,
+            )
+            .add_systems(
+                Update,
+                save_and_quit
+                    .run_if(in_state(RegionMode::Live))
+                    .run_if(resource_exists::<RegionSim>),
+            );
+    }
+}
+
+/// A saved match to play on instead of starting a new one.
+#[derive(Resource)]
+pub struct ResumedMatch {
+    pub world: RegionWorld,
+    pub recorder: Recorder,
+    /// The chart of the match so far, sampled while it was played again.
+    pub chart: TerritoryChart,
+    /// Its capture heatmap, counted the same way.
+    pub heat: CaptureHeat,
+}
+
+#[derive(Component)]
+struct SaveButton;
+
+pub fn has_saved_match() -> bool {
+    Path::new(SAVE_PATH).exists()
+}
+
+/// Loads the saved match. A save can only be continued once.
+pub fn load_saved_match() -> Result<ResumedMatch, String> {
+    let bytes = fs::read(SAVE_PATH).map_err(|error| format!("{SAVE_PATH}: {error}"))?;
+    let mut chart = TerritoryChart::default();
+    let mut heat = CaptureHeat::default();
+    let (world, recorder) = SavedMatch::from_bytes(&bytes)
+        .and_then(|saved| {
+            saved.resume(|world| {
+                chart.sample(world);
+                heat.count(world);
+            })
+        })
+        .map_err(|error| format!("{SAVE_PATH}: {error}"))?;
+    if let Err(error) = fs::remove_file(SAVE_PATH) {
+        warn!("could not remove {SAVE_PATH}: {error}");
+    }
+    Ok(ResumedMatch {
+        world,
+        recorder,
+        chart,
+        heat,
+    })
+}
+
+fn setup_save_button(mut commands: Commands, asset_server: Res<AssetServer>) {
+    commands
+        .spawn((
+            Button,
+            Node {
+                position_type: PositionType::Absolute,
+                top: Val::Px(70.0),
+                left: Val::Px(10.),
+                padding: UiRect::horizontal(Val::Px(8.0)),
+                ..default()
+            },
+            BackgroundColor(NORMAL_BUTTON),
+            SaveButton,
+        ))
+        .with_child((
+            Text::new("Save & Quit"),
+            TextFont {
+                font: asset_server.load(FIRASANS_FONT),
+                font_size: 30.0,
+                ..default()
+            },
+            TextColor(TEXT_COLOR),
+        ));
+}
+
+fn save_and_quit(
+    interaction_query: Query<&Interaction, (Changed<Interaction>, With<SaveButton>)>,
+    mut commands: Commands,
+    sim: Res<RegionSim>,
+    recorder: Option<Res<MatchRecorder>>,
+    mut game_state: ResMut<NextState<GameState>>,
+    mut pause_state: ResMut<NextState<PauseState>>,
+) {
+    for interaction in &interaction_query {
+        if *interaction != Interaction::Pressed {
+            continue;
+        }
+        // a finished match has nothing left to continue
+        let Some(recorder) = recorder.as_ref().filter(|_| sim.outcome().is_none()) else {
+            game_state.set(GameState::Menu);
+            continue;
+        };
+        let bytes = SavedMatch::new(recorder, &sim).to_bytes();
+        match fs::create_dir_all(SAVE_DIR).and_then(|_| fs::write(SAVE_PATH, bytes)) {
+            Ok(()) => {
+                info!("saved match to {SAVE_PATH}");
+                // the replay is saved once the continued match ends
+                commands.remove_resource::<MatchRecorder>();
+                game_state.set(GameState::Menu);
+            }
+            Err(error) => {
+                warn!("could not save match to {SAVE_PATH}: {error}");
+                commands
+                    .insert_resource(PauseMessage(format!("Could not save the match: {error}")));
+                pause_state.set(PauseState::Paused);
+            }
+        }
+    }
+}
@@ -0,0 +1,763 @@
+use std::path::PathBuf;
+
+use bevy::{
+    asset::{LoadState, LoadedFolder},
+    prelude::*,
+};
+
+use crate::{
+    common::*,
+    region_game::RegionMode,
+    region_maps::{MapFolder, RegionMapAsset},
+    region_power_ups::power_up_title,
+    region_replay::{load_replay, replay_age, saved_replays, ReplayViewer},
+    region_save::{has_saved_match, load_saved_match},
+    region_sim::{Layout, MatchSettings, PowerUpKind, SpeedCurve, StalemateResponse, TICK_SECONDS},
+    utils::{common_button_system, despawn_with_component, EntitySpawner, SelectedOption},
+    GameState,
+};
+
+pub struct RegionSetupPlugin;
+
+impl Plugin for RegionSetupPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<RegionSettings>()
+            .add_systems(OnEnter(GameState::RegionSetup), setup_screen)
+            .add_systems(
+                OnExit(GameState::RegionSetup),
+                despawn_with_component::<OnRegionSetupScreen>,
+            )
+            .add_systems(
+                Update,
+                (
+                    fill_map_list,
+                    arena_button,
+                    power_up_button,
+                    rule_button,
+                    stalemate_button,
+                    speed_curve_button,
+                    replay_picker,
+                    common_button_system,
+                    setup_action,
+                )
+                    .run_if(in_state(GameState::RegionSetup)),
+            );
+    }
+}
+
+/// Settings for the next Region Battle match, picked on the setup screen.
+#[derive(Resource, Default, Deref, DerefMut)]
+pub struct RegionSettings(pub MatchSettings);
+
+#[derive(Component)]
+struct OnRegionSetupScreen;
+
+#[derive(Component)]
+enum SetupButtonAction {
+    Start,
+    Continue,
+    Replay,
+    Back,
+}
+
+// Says why the last replay or the saved match could not be loaded
+#[derive(Component)]
+struct LoadError;
+
+// Picks a generated layout or one of the map files as the arena
+#[derive(Component, Clone, PartialEq)]
+enum ArenaButton {
+    Layout(Layout),
+    Map(Handle<RegionMapAsset>),
+}
+
+/// Turns a power-up on or off for the next match.
+#[derive(Component)]
+struct PowerUpButton(PowerUpKind);
+
+/// Turns one of the optional match rules on or off.
+#[derive(Component, Clone, Copy)]
+enum RuleButton {
+    TwoPlayers,
+    Boost,
+    Barriers,
+    BallCollisions,
+    ContactSteals,
+}
+
+impl RuleButton {
+    const ALL: [RuleButton; 5] = [
+        RuleButton::TwoPlayers,
+        RuleButton::Boost,
+        RuleButton::Barriers,
+        RuleButton::BallCollisions,
+        RuleButton::ContactSteals,
+    ];
+
+    fn title(self) -> &'static str {
+        match self {
+            RuleButton::TwoPlayers => "Two Players",
+            RuleButton::Boost => "Boost Meter",
+            RuleButton::Barriers => "Barriers",
+            RuleButton::BallCollisions => "Ball Collisions",
+            RuleButton::ContactSteals => "Contact Steals",
+        }
+    }
+
+    fn setting(self, settings: &mut MatchSettings) -> &mut bool {
+        match self {
+            RuleButton::TwoPlayers => &mut settings.steering,
+            RuleButton::Boost => &mut settings.boost,
+            RuleButton::Barriers => &mut settings.barriers,
+            RuleButton::BallCollisions => &mut settings.ball_collisions,
+            RuleButton::ContactSteals => &mut settings.contact_steals,
+        }
+    }
+}
+
+/// Cycles through the responses to a stalemate, and turns detection off.
+#[derive(Component)]
+struct StalemateButton;
+
+fn stalemate_title(response: Option<StalemateResponse>) -> &'static str {
+    match response {
+        Some(StalemateResponse::Jitter) => "Loops: Jitter",
+        Some(StalemateResponse::SpeedUp) => "Loops: Speed Up",
+        Some(StalemateResponse::Draw) => "Loops: Draw",
+        None => "Loops: Allowed",
+    }
+}
+
+/// Cycles through `SPEED_CURVES`.
+#[derive(Component)]
+struct SpeedCurveButton;
+
+struct SpeedPreset {
+    title: &'static str,
+    curve: SpeedCurve,
+    tick_limit: Option<u32>,
+}
+
+// The first preset keeps the speed constant. Sudden death needs a time limit,
+// three minutes with the last thirty seconds sped up.
+const SPEED_CURVES: [SpeedPreset; 4] = [
+    SpeedPreset {
+        title: "Speed: Constant",
+        curve: SpeedCurve {
+            per_capture: 0.,
+            per_second: 0.,
+            max_speed: 1.,
+            sudden_death: None,
+        },
+        tick_limit: None,
+    },
+    SpeedPreset {
+        title: "Speed: Captures",
+        curve: SpeedCurve {
+            per_capture: 0.005,
+            per_second: 0.,
+            max_speed: 2.,
+            sudden_death: None,
+        },
+        tick_limit: None,
+    },
+    SpeedPreset {
+        title: "Speed: Time",
+        curve: SpeedCurve {
+            per_capture: 0.,
+            per_second: 0.01,
+            max_speed: 2.,
+            sudden_death: None,
+        },
+        tick_limit: None,
+    },
+    SpeedPreset {
+        title: "Sudden Death",
+        curve: SpeedCurve {
+            per_capture: 0.,
+            per_second: 0.01,
+            max_speed: 1.5,
+            sudden_death: Some((30. / TICK_SECONDS) as u32),
+        },
+        tick_limit: Some((180. / TICK_SECONDS) as u32),
+    },
+];
+
+fn speed_preset(settings: &MatchSettings) -> usize {
+    SPEED_CURVES
+        .iter()
+        .position(|preset| {
+            preset.curve == settings.speed_curve && preset.tick_limit == settings.tick_limit
+        })
+        .unwrap_or(0)
+}
+
+/// Cycles through the saved replays, newest first, for the Replay button.
+#[derive(Component)]
+struct ReplayPicker {
+    replays: Vec<PathBuf>,
+    index: usize,
+}
+
+impl ReplayPicker {
+    fn title(&self) -> String {
+        format!(
+            "{}: {}",
+            self.index + 1,
+            replay_age(&self.replays[self.index])
+        )
+    }
+}
+
+// Filled with map buttons once `assets/maps/` has finished loading
+#[derive(Component)]
+struct MapList;
+
+fn layout_title(layout: Layout) -> &'static str {
+    match layout {
+        Layout::VerticalHalves => "Vertical",
+        Layout::HorizontalHalves => "Horizontal",
+        Layout::DiagonalHalves => "Diagonal",
+        Layout::Checkerboard => "Checkers",
+        Layout::Rings => "Rings",
+        Layout::RandomBlobs => "Blobs",
+        Layout::FairRandom => "Fair Random",
+    }
+}
+
+fn setup_screen(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    settings: Res<RegionSettings>,
+) {
+    let font = asset_server.load(FIRASANS_FONT);
+
+    commands
+        .spawn((
+            Node {
+                width: Val::Percent(100.0),
+                height: Val::Percent(100.0),
+                flex_direction: FlexDirection::Column,
+                align_items: AlignItems::Center,
+                justify_content: JustifyContent::Center,
+                ..default()
+            },
+            OnRegionSetupScreen,
+        ))
+        .with_children(|parent| {
+            parent.spawn((
+                Text::new("STARTING LAYOUT"),
+                TextFont {
+                    font: font.clone(),
+                    font_size: 60.0,
+                    ..Default::default()
+                },
+                TextColor(TEXT_COLOR),
+                Node {
+                    margin: UiRect::all(Val::Px(30.0)),
+                    ..default()
+                },
+            ));
+
+            parent
+                .spawn(Node {
+                    width: Val::Px(980.0),
+                    flex_wrap: FlexWrap::Wrap,
+                    justify_content: JustifyContent::Center,
+                    ..default()
+                })
+                .with_children(|parent| {
+                    for layout in Layout::ALL {
+                        let selected = settings.map.is_none() && layout == settings.layout;
+                        spawn_option_button(
+                            parent,
+                            ArenaButton::Layout(layout),
+                            layout_title(layout),
+                            selected,
+                            &font,
+                        );
+                    }
+                });
+
+            spawn_heading(parent, "MAPS", &font);
+
+            parent.spawn((
+                Node {
+                    width: Val::Px(980.0),
+                    flex_wrap: FlexWrap::Wrap,
+                    justify_content: JustifyContent::Center,
+                    ..default()
+                },
+                MapList,
+            ));
+
+            spawn_heading(parent, "RULES", &font);
+            parent
+                .spawn(Node {
+                    width: Val::Px(980.0),
+                    flex_wrap: FlexWrap::Wrap,
+                    justify_content: JustifyContent::Center,
+                    ..default()
+                })
+                .with_children(|parent| {
+                    for button in RuleButton::ALL {
+                        let selected = match button {
+                            RuleButton::TwoPlayers => settings.steering,
+                            RuleButton::Boost => settings.boost,
+                            RuleButton::Barriers => settings.barriers,
+                            RuleButton::BallCollisions => settings.ball_collisions,
+                            RuleButton::ContactSteals => settings.contact_steals,
+                        };
+                        spawn_option_button(parent, button, button.title(), selected, &font);
+                    }
+                    spawn_option_button(
+                        parent,
+                        StalemateButton,
+                        stalemate_title(settings.stalemate),
+                        settings.stalemate.is_some(),
+                        &font,
+                    );
+                    let preset = speed_preset(&settings);
+                    spawn_option_button(
+                        parent,
+                        SpeedCurveButton,
+                        SPEED_CURVES[preset].title,
+                        preset != 0,
+                        &font,
+                    );
+                });
+
+            spawn_heading(parent, "POWER-UPS", &font);
+            parent
+                .spawn(Node {
+                    width: Val::Px(980.0),
+                    flex_wrap: FlexWrap::Wrap,
+                    justify_content: JustifyContent::Center,
+                    ..default()
+                })
+                .with_children(|parent| {
+                    for kind in PowerUpKind::ALL {
+                        spawn_option_button(
+                            parent,
+                            PowerUpButton(kind),
+                            power_up_title(kind),
+                            settings.power_ups.contains(&kind),
+                            &font,
+                        );
+                    }
+                });
+
+            parent.spawn(Node::default()).with_children(|parent| {
+                if has_saved_match() {
+                    parent.spawn_button(
+                        SetupButtonAction::Continue,
+                        "right.png",
+                        "Continue",
+                        &asset_server,
+                    );
+                }
+                parent.spawn_button(
+                    SetupButtonAction::Start,
+                    "right.png",
+                    "Start",
+                    &asset_server,
+                );
+                let mut replays = saved_replays();
+                if !replays.is_empty() {
+                    replays.reverse();
+                    let picker = ReplayPicker { replays, index: 0 };
+                    let title = picker.title();
+                    spawn_option_button(parent, picker, &title, false, &font);
+                }
+                parent.spawn_button(
+                    SetupButtonAction::Replay,
+                    "right.png",
+                    "Replay",
+                    &asset_server,
+                );
+                parent.spawn_button(
+                    SetupButtonAction::Back,
+                    "exitRight.png",
+                    "Back",
+                    &asset_server,
+                );
+            });
+            parent.spawn((
+                Text::default(),
+                TextFont {
+                    font: font.clone(),
+                    font_size: 20.0,
+                    ..Default::default()
+                },
+                TextColor(TEXT_COLOR),
+                LoadError,
+            ));
+        });
+}
+
+fn spawn_heading(parent: &mut ChildBuilder, title: &str, font: &Handle<Font>) {
+    parent.spawn((
+        Text::new(title),
+        TextFont {
+            font: font.clone(),
+            font_size: 40.0,
+            ..Default::default()
+        },
+        TextColor(TEXT_COLOR),
+        Node {
+            margin: UiRect::all(Val::Px(10.0)),
+            ..default()
+        },
+    ));
+}
+
+// A smaller button than `spawn_button`, so every option fits on one screen
+fn spawn_option_button(
+    parent: &mut ChildBuilder,
+    bundle: impl Bundle,
+    title: &str,
+    selected: bool,
+    font: &Handle<Font>,
+) {
+    let color = if selected {
+        PRESSED_BUTTON
+    } else {
+        NORMAL_BUTTON
+    };
+    let mut button = parent.spawn((
+        Button,
+        Node {
+            width: Val::Px(180.0),
+            height: Val::Px(60.0),
+            margin: UiRect::all(Val::Px(8.0)),
+            justify_content: JustifyContent::Center,
+            align_items: AlignItems::Center,
+            ..default()
+        },
+        BackgroundColor(color),
+        bundle,
+    ));
+    if selected {
+        button.insert(SelectedOption);
+    }
+    button.with_child((
+        Text::new(title),
+        TextFont {
+            font: font.clone(),
+            font_size: 22.0,
+            ..Default::default()
+        },
+        TextColor(TEXT_COLOR),
+    ));
+}
+
+// Adds a button per map once every file in the folder has loaded or failed.
+// Broken maps are listed with their parse error instead of a button.
+fn fill_map_list(
+    mut commands: Commands,
+    list_query: Query<Entity, With<MapList>>,
+    asset_server: Res<AssetServer>,
+    map_folder: Res<MapFolder>,
+    folders: Res<Assets<LoadedFolder>>,
+    maps: Res<Assets<RegionMapAsset>>,
+    settings: Res<RegionSettings>,
+) {
+    let Ok(list) = list_query.get_single() else {
+        return;
+    };
+    let Some(folder) = folders.get(&map_folder.0) else {
+        return;
+    };
+    let handles: Vec<Handle<RegionMapAsset>> = folder
+        .handles
+        .iter()
+        .filter_map(|handle| handle.clone().try_typed().ok())
+        .collect();
+    let settled = |handle: &Handle<RegionMapAsset>| {
+        matches!(
+            asset_server.get_load_state(handle),
+            Some(LoadState::Loaded | LoadState::Failed(_))
+        )
+    };
+    if !handles.iter().all(settled) {
+        return;
+    }
+
+    let font = asset_server.load(FIRASANS_FONT);
+    commands
+        .entity(list)
+        .remove::<MapList>()
+        .with_children(|parent| {
+            for handle in handles {
+                if let Some(map) = maps.get(&handle) {
+                    let selected = settings.map.as_ref() == Some(&map.0);
+                    spawn_option_button(
+                        parent,
+                        ArenaButton::Map(handle),
+                        &map.name,
+                        selected,
+                        &font,
+                    );
+                } else if let Some(LoadState::Failed(error)) = asset_server.get_load_state(&handle)
+                {
+                    parent.spawn((
+                        Text::new(error.to_string()),
+                        TextFont {
+                            font: font.clone(),
+                            font_size: 20.0,
+                            ..Default::default()
+                        },
+                        TextColor(TEXT_COLOR),
+                        Node {
+                            width: Val::Percent(100.0),
+                            margin: UiRect::all(Val::Px(5.0)),
+                            ..default()
+                        },
+                    ));
+                }
+            }
+        });
+}
+
+// Moves the selection to the pressed arena button and remembers the choice
+#[allow(clippy::type_complexity)]
+fn arena_button(
+    interaction_query: Query<(&Interaction, &ArenaButton, Entity), Changed<Interaction>>,
+    mut selected_query: Query<
+        (Entity, &mut BackgroundColor),
+        (With<SelectedOption>, With<ArenaButton>),
+    >,
+    mut commands: Commands,
+    mut settings: ResMut<RegionSettings>,
+    maps: Res<Assets<RegionMapAsset>>,
+) {
+    for (interaction, button, entity) in &interaction_query {
+        if *interaction != Interaction::Pressed {
+            continue;
+        }
+        match button {
+            ArenaButton::Layout(layout) => {
+                settings.layout = *layout;
+                settings.map = None;
+            }
+            ArenaButton::Map(handle) => {
+                let Some(map) = maps.get(handle) else {
+                    continue;
+                };
+                settings.map = Some(map.0.clone());
+            }
+        }
+        for (previous, mut color) in &mut selected_query {
+            *color = NORMAL_BUTTON.into();
+            commands.entity(previous).remove::<SelectedOption>();
+        }
+        commands.entity(entity).insert(SelectedOption);
+    }
+}
+
+// Toggles the pressed power-up, any number of them can be on at once
+fn power_up_button(
+    interaction_query: Query<(&Interaction, &PowerUpButton, Entity), Changed<Interaction>>,
+    mut commands: Commands,
+    mut settings: ResMut<RegionSettings>,
+) {
+    for (interaction, button, entity) in &interaction_query {
+        if *interaction != Interaction::Pressed {
+            continue;
+        }
+        let enabled = !settings.power_ups.contains(&button.0);
+        if enabled {
+            commands.entity(entity).insert(SelectedOption);
+        } else {
+            commands.entity(entity).remove::<SelectedOption>();
+        }
+        // kept in `PowerUpKind::ALL` order so equal choices give equal settings
+        settings.power_ups = PowerUpKind::ALL
+            .into_iter()
+            .filter(|kind| {
+                if *kind == button.0 {
+                    enabled
+                } else {
+                    settings.power_ups.contains(kind)
+                }
+            })
+            .collect();
+    }
+}
+
+// WASD steers red and the arrow keys blue, see `region_game::STEERING_KEYS`.
+// Barriers are placed with the left (red) and right (blue) mouse buttons.
+fn rule_button(
+    interaction_query: Query<(&Interaction, &RuleButton, Entity), Changed<Interaction>>,
+    mut commands: Commands,
+    mut settings: ResMut<RegionSettings>,
+) {
+    for (interaction, button, entity) in &interaction_query {
+        if *interaction != Interaction::Pressed {
+            continue;
+        }
+        let setting = button.setting(&mut settings);
+        *setting = !*setting;
+        if *setting {
+            commands.entity(entity).insert(SelectedOption);
+        } else {
+            commands.entity(entity).remove::<SelectedOption>();
+        }
+    }
+}
+
+#[allow(clippy::type_complexity)]
+fn stalemate_button(
+    interaction_query: Query<
+        (&Interaction, &Children, Entity),
+        (Changed<Interaction>, With<StalemateButton>),
+    >,
+    mut text_query: Query<&mut Text>,
+    mut commands: Commands,
+    mut settings: ResMut<RegionSettings>,
+) {
+    for (interaction, children, entity) in &interaction_query {
+        if *interaction != Interaction::Pressed {
+            continue;
+        }
+        settings.stalemate = match settings.stalemate {
+            Some(StalemateResponse::Jitter) => Some(StalemateResponse::SpeedUp),
+            Some(StalemateResponse::SpeedUp) => Some(StalemateResponse::Draw),
+            Some(StalemateResponse::Draw) => None,
+            None => Some(StalemateResponse::Jitter),
+        };
+        if settings.stalemate.is_some() {
+            commands.entity(entity).insert(SelectedOption);
+        } else {
+            commands.entity(entity).remove::<SelectedOption>();
+        }
+        let mut text = text_query.get_mut(children[0]).unwrap();
+        **text = stalemate_title(settings.stalemate).to_string();
+    }
+}
+
+#[allow(clippy::type_complexity)]
+fn speed_curve_button(
+    interaction_query: Query<
+        (&Interaction, &Children, Entity),
+        (Changed<Interaction>, With<SpeedCurveButton>),
+    >,
+    mut text_query: Query<&mut Text>,
+    mut commands: Commands,
+    mut settings: ResMut<RegionSettings>,
+) {
+    for (interaction, children, entity) in &interaction_query {
+        if *interaction != Interaction::Pressed {
+            continue;
+        }
+        let preset = (speed_preset(&settings) + 1) % SPEED_CURVES.len();
+        settings.speed_curve = SPEED_CURVES[preset].curve;
+        settings.tick_limit = SPEED_CURVES[preset].tick_limit;
+        if preset != 0 {
+            commands.entity(entity).insert(SelectedOption);
+        } else {
+            commands.entity(entity).remove::<SelectedOption>();
+        }
+        let mut text = text_query.get_mut(children[0]).unwrap();
+        **text = SPEED_CURVES[preset].title.to_string();
+    }
+}
+
+fn replay_picker(
+    mut interaction_query: Query<
+        (&Interaction, &Children, &mut ReplayPicker),
+        Changed<Interaction>,
+    >,
+    mut text_query: Query<&mut Text>,
+) {
+    for (interaction, children, mut picker) in &mut interaction_query {
+        if *interaction != Interaction::Pressed {
+            continue;
+        }
+        picker.index = (picker.index + 1) % picker.replays.len();
+        let mut text = text_query.get_mut(children[0]).unwrap();
+        **text = picker.title();
+    }
+}
+
+fn setup_action(
+    interaction_query: Query<(&Interaction, &SetupButtonAction), Changed<Interaction>>,
+    picker_query: Query<&ReplayPicker>,
+    mut error_query: Query<&mut Text, With<LoadError>>,
+    mut commands: Commands,
+    mut settings: ResMut<RegionSettings>,
+    mut game_state: ResMut<NextState<GameState>>,
+    mut region_mode: ResMut<NextState<RegionMode>>,
+) {
+    for (interaction, action) in &interaction_query {
+        if *interaction != Interaction::Pressed {
+            continue;
+        }
+        match action {
+            SetupButtonAction::Start => {
+                // random layouts get a fresh board every match, maps fresh
+                // launch angles
+                settings.seed =
+                    (settings.map.is_some() || settings.layout.is_random()).then(rand::random);
+                game_state.set(GameState::RegionGame);
+            }
+            SetupButtonAction::Continue => match load_saved_match() {
+                Ok(resumed) => {
+                    commands.insert_resource(resumed);
+                    game_state.set(GameState::RegionGame);
+                }
+                Err(error) => {
+                    for mut text in &mut error_query {
+                        **text = error.clone();
+                    }
+                }
+            },
+            SetupButtonAction::Replay => {
+                let loaded = match picker_query.get_single() {
+                    Ok(picker) => load_replay(&picker.replays[picker.index]),
+                    Err(_) => Err("no replays recorded yet".to_string()),
+                };
+                match loaded {
+                    Ok(replay) => {
+                        commands.insert_resource(ReplayViewer::new(replay));
+                        game_state.set(GameState::RegionGame);
+                        region_mode.set(RegionMode::Replay);
+                    }
+                    Err(error) => {
+                        for mut text in &mut error_query {
+                            **text = error.clone();
+                        }
+                    }
+                }
+            }
+            SetupButtonAction::Back => game_state.set(GameState::Menu),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn count<T: Component>(app: &mut App) -> usize {
+        let world = app.world_mut();
+        world.query_filtered::<(), With<T>>().iter(world).count()
+    }
+
+    #[test]
+    fn every_option_button_is_spawned() {
+        let mut app = App::new();
+        app.add_plugins((MinimalPlugins, AssetPlugin::default()))
+            .init_asset::<Font>()
+            .init_asset::<Image>()
+            .init_resource::<RegionSettings>()
+            .add_systems(Startup, setup_screen);
+        app.update();
+
+        assert_eq!(count::<ArenaButton>(&mut app), Layout::ALL.len());
+        assert_eq!(count::<RuleButton>(&mut app), RuleButton::ALL.len());
+        assert_eq!(count::<StalemateButton>(&mut app), 1);
+        assert_eq!(count::<SpeedCurveButton>(&mut app), 1);
+        assert_eq!(count::<PowerUpButton>(&mut app), PowerUpKind::ALL.len());
+    }
+}
@@ -0,0 +1,1670 @@
+// Region Battle rules without any ECS state.
+//
+// `RegionWorld` owns the board and the balls as plain data and advances them with
+// `step`, so the rules can be tested, benchmarked and reused outside the renderer.
+// `region_game` is only a thin adapter that mirrors the world into sprites.
+mod barrier;
+mod history;
+mod layout;
+mod map;
+mod power_up;
+mod replay;
+mod save;
+mod speed_curve;
+mod stalemate;
+mod steering;
+
+use std::f32::consts::FRAC_PI_2;
+
+use bevy::math::{
+    bounding::{Aabb2d, BoundingCircle, BoundingVolume},
+    Vec2,
+};
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+pub use barrier::{Barrier, BarrierStock};
+pub use history::History;
+pub use layout::Layout;
+pub use map::{MapError, RegionMap};
+pub use power_up::{Effect, PickedUp, Pickup, PowerUpKind, PICKUP_RADIUS};
+
+pub use replay::{Playback, Recorder, Replay, ReplayInput};
+pub use save::SavedMatch;
+pub use speed_curve::SpeedCurve;
+pub use stalemate::{Stalemate, StalemateResponse};
+pub use steering::SteeringInput;
+
+use barrier::BARRIER_COOLDOWN;
+use power_up::{GROWTH, MAX_PICKUPS, PICKUP_INTERVAL, SPEED_BOOST, SPLIT_ANGLE};
+use speed_curve::SUDDEN_DEATH_SPEED;
+use stalemate::Detector;
+use steering::Steering;
+
+pub const BRICK_WIDTH: f32 = 20.;
+pub const BRICK_COUNT_WIDTH: usize = 31;
+pub const BALL_RADIUS: f32 = 10.;
+pub const BALL_SPEED: f32 = 100.;
+pub const WALL_THICKNESS: f32 = 40.0;
+// Bevy's default `Time<Fixed>` rate, and the time each tick simulates at any speed
+pub const TICK_SECONDS: f32 = 1. / 64.;
+/// Bumped whenever the same settings and inputs can play out differently,
+/// after a physics, rules or settings default change. Replays and saved
+/// matches of another version are rejected up front instead of drifting.
+pub const SIM_VERSION: u32 = 1;
+// how far a stalemate jitter turns each ball, in radians
+const JITTER_ANGLE: std::ops::Range<f32> = 0.05..0.25;
+const STALEMATE_SPEED_UP: f32 = 1.25;
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct TeamId(pub u8);
+
+impl TeamId {
+    pub const RED: TeamId = TeamId(0);
+    pub const BLUE: TeamId = TeamId(1);
+    pub const ALL: [TeamId; 2] = [TeamId::RED, TeamId::BLUE];
+}
+
+/// Enemy hits an armored brick takes before it is captured.
+pub const ARMOR_HITS: u8 = 3;
+
+/// One square of the board.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Cell {
+    /// A brick owned by a team. Balls of the other teams bounce off and capture
+    /// it, balls of the owner pass through.
+    Team(TeamId),
+    /// A brick nobody owns yet, claimed by the first ball to hit it.
+    Neutral,
+    /// A brick that needs `hits` more hits from other teams before it is
+    /// captured, and then turns into a plain `Team` brick. `owner` is `None` for
+    /// armored neutral bricks.
+    Armored { owner: Option<TeamId>, hits: u8 },
+    /// Blocks every ball and never changes hands.
+    Wall,
+    /// Not part of the arena. Blocks every ball like a wall but is not drawn.
+    Void,
+}
+
+impl Cell {
+    pub fn owner(&self) -> Option<TeamId> {
+        match self {
+            Cell::Team(team) => Some(*team),
+            Cell::Armored { owner, .. } => *owner,
+            _ => None,
+        }
+    }
+
+    /// Whether a ball of `team` bounces off this cell.
+    pub fn blocks(&self, team: TeamId) -> bool {
+        self.owner() != Some(team)
+    }
+
+    /// Whether the cell counts towards the board, walls and void never do.
+    pub fn is_capturable(&self) -> bool {
+        !matches!(self, Cell::Wall | Cell::Void)
+    }
+
+    pub fn capturable_by(&self, team: TeamId) -> bool {
+        self.is_capturable() && self.owner() != Some(team)
+    }
+}
+
+/// Knobs for a single match, picked on the setup screen or on the headless
+/// runner's command line.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MatchSettings {
+    /// Bricks along each side of the square board.
+    pub board_size: usize,
+    /// Launch speed of the red and blue balls.
+    pub ball_speeds: [f32; 2],
+    pub balls_per_team: usize,
+    pub layout: Layout,
+    /// Seeds the random layouts and the launch directions. `None` launches both
+    /// balls diagonally, as in the classic match.
+    pub seed: Option<u64>,
+    /// Hand-made arena replacing the generated board, its walls and the default
+    /// starting positions. `layout`, `board_size` and `balls_per_team` are ignored.
+    pub map: Option<RegionMap>,
+    /// Power-ups that can appear during the match, empty turns them off.
+    pub power_ups: Vec<PowerUpKind>,
+    /// Balls turn towards the direction their team sets with
+    /// `RegionWorld::steer`, for matches between two players.
+    pub steering: bool,
+    /// Gives each steering team a meter it can spend on a burst of speed.
+    pub boost: bool,
+    /// Lets each team place a few short-lived barriers with
+    /// `RegionWorld::place_barrier`.
+    pub barriers: bool,
+    /// Balls of different teams bounce off each other instead of passing
+    /// through.
+    pub ball_collisions: bool,
+    /// How much of their closing speed colliding balls keep, `1` is perfectly
+    /// elastic. Lower values slow the balls down for good.
+    pub restitution: f32,
+    /// When two balls collide, the one hitting harder captures the cell where
+    /// they touch.
+    pub contact_steals: bool,
+    /// What to do when the match is stuck in a loop, `None` lets it loop.
+    pub stalemate: Option<StalemateResponse>,
+    /// How the balls speed up over the match.
+    pub speed_curve: SpeedCurve,
+    pub tick_limit: Option<u32>,
+}
+
+impl Default for MatchSettings {
+    fn default() -> Self {
+        Self {
+            board_size: BRICK_COUNT_WIDTH,
+            ball_speeds: [BALL_SPEED; 2],
+            balls_per_team: 1,
+            layout: Layout::default(),
+            seed: None,
+            map: None,
+            power_ups: Vec::new(),
+            steering: false,
+            boost: false,
+            barriers: false,
+            ball_collisions: false,
+            restitution: 1.,
+            contact_steals: false,
+            stalemate: None,
+            speed_curve: SpeedCurve::default(),
+            tick_limit: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MatchOutcome {
+    Winner(TeamId),
+    Draw,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Ball {
+    pub team: TeamId,
+    pub position: Vec2,
+    pub velocity: Vec2,
+    pub radius: f32,
+}
+
+impl Ball {
+    pub fn new(team: TeamId, position: Vec2, velocity: Vec2) -> Self {
+        Self {
+            team,
+            position,
+            velocity,
+            radius: BALL_RADIUS,
+        }
+    }
+
+    fn bounding_circle(&self) -> BoundingCircle {
+        BoundingCircle::new(self.position, self.radius)
+    }
+
+    fn reflect(&mut self, normal: Vec2) {
+        self.velocity -= 2. * self.velocity.dot(normal) * normal;
+    }
+
+    /// Grows with the area, so a grown ball shoves normal ones around.
+    pub fn mass(&self) -> f32 {
+        (self.radius / BALL_RADIUS).powi(2)
+    }
+}
+
+// Separates two overlapping balls and exchanges momentum along the line between
+// their centers. `restitution` is `1` for a perfectly elastic bounce. Returns the
+// contact point, or `None` if the balls do not touch.
+fn collide_balls(a: &mut Ball, b: &mut Ball, restitution: f32) -> Option<Vec2> {
+    let offset = b.position - a.position;
+    let distance = offset.length();
+    let reach = a.radius + b.radius;
+    if distance >= reach {
+        return None;
+    }
+    // balls on the exact same spot push apart along an arbitrary axis
+    let normal = offset.try_normalize().unwrap_or(Vec2::X);
+    let (inverse_a, inverse_b) = (1. / a.mass(), 1. / b.mass());
+    let share_a = inverse_a / (inverse_a + inverse_b);
+    let overlap = reach - distance;
+    a.position -= normal * overlap * share_a;
+    b.position += normal * overlap * (1. - share_a);
+
+    // only balls moving towards each other exchange momentum
+    let closing = (a.velocity - b.velocity).dot(normal);
+    if closing > 0. {
+        let impulse = (1. + restitution) * closing / (inverse_a + inverse_b);
+        a.velocity -= normal * impulse * inverse_a;
+        b.velocity += normal * impulse * inverse_b;
+    }
+    Some(a.position + normal * a.radius)
+}
+
+/// Which side of the arena is this wall located on?
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WallLocation {
+    Left,
+    Right,
+    Bottom,
+    Top,
+}
+
+impl WallLocation {
+    pub const ALL: [WallLocation; 4] = [
+        WallLocation::Left,
+        WallLocation::Right,
+        WallLocation::Bottom,
+        WallLocation::Top,
+    ];
+
+    // `half_extent` is the distance from the arena center to the middle of the wall
+    fn position(&self, half_extent: Vec2) -> Vec2 {
+        match self {
+            WallLocation::Left => Vec2::new(-half_extent.x, 0.),
+            WallLocation::Right => Vec2::new(half_extent.x, 0.),
+            WallLocation::Bottom => Vec2::new(0., -half_extent.y),
+            WallLocation::Top => Vec2::new(0., half_extent.y),
+        }
+    }
+
+    fn size(&self, half_extent: Vec2, thickness: f32) -> Vec2 {
+        let arena_height = half_extent.y * 2.;
+        let arena_width = half_extent.x * 2.;
+        // Make sure we haven't messed up our constants
+        assert!(arena_height > 0.0);
+        assert!(arena_width > 0.0);
+
+        match self {
+            WallLocation::Left | WallLocation::Right => {
+                Vec2::new(thickness, arena_height + thickness)
+            }
+            WallLocation::Bottom | WallLocation::Top => {
+                Vec2::new(arena_width + thickness, thickness)
+            }
+        }
+    }
+
+    pub fn aabb(&self, half_extent: Vec2, thickness: f32) -> Aabb2d {
+        Aabb2d::new(
+            self.position(half_extent),
+            self.size(half_extent, thickness) / 2.,
+        )
+    }
+}
+
+/// Maps cell indices to world space. Cell `0` is the bottom-left brick and the
+/// grid is centered on the origin.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Grid {
+    pub columns: usize,
+    pub rows: usize,
+    pub brick_size: f32,
+}
+
+impl Grid {
+    pub fn len(&self) -> usize {
+        self.columns * self.rows
+    }
+
+    pub fn coords(&self, cell: usize) -> (usize, usize) {
+        (cell % self.columns, cell / self.columns)
+    }
+
+    pub fn center(&self, cell: usize) -> Vec2 {
+        let (column, row) = self.coords(cell);
+        Vec2::new(
+            (column as f32 - (self.columns - 1) as f32 / 2.) * self.brick_size,
+            (row as f32 - (self.rows - 1) as f32 / 2.) * self.brick_size,
+        )
+    }
+
+    pub fn aabb(&self, cell: usize) -> Aabb2d {
+        Aabb2d::new(self.center(cell), Vec2::splat(self.brick_size / 2.))
+    }
+
+    pub fn cell_at(&self, point: Vec2) -> Option<usize> {
+        let corner = -Vec2::new(self.columns as f32, self.rows as f32) * self.brick_size / 2.;
+        let coords = ((point - corner) / self.brick_size).floor();
+        if coords.x < 0. || coords.y < 0. {
+            return None;
+        }
+        let (column, row) = (coords.x as usize, coords.y as usize);
+        (column < self.columns && row < self.rows).then(|| row * self.columns + column)
+    }
+
+    /// Cells whose square may overlap `aabb`, in index order. Only the handful of
+    /// cells under a ball are visited instead of the whole board.
+    pub fn cells_overlapping(&self, aabb: Aabb2d) -> impl Iterator<Item = usize> {
+        let corner = -Vec2::new(self.columns as f32, self.rows as f32) * self.brick_size / 2.;
+        let min = ((aabb.min - corner) / self.brick_size).floor();
+        let max = ((aabb.max - corner) / self.brick_size).floor();
+        let span = |low: f32, high: f32, count: usize| {
+            let high = high.min(count as f32 - 1.);
+            let low = low.max(0.);
+            if high < low {
+                0..0
+            } else {
+                low as usize..high as usize + 1
+            }
+        };
+        let columns = span(min.x, max.x, self.columns);
+        let rows = span(min.y, max.y, self.rows);
+        let width = self.columns;
+        rows.flat_map(move |row| columns.clone().map(move |column| row * width + column))
+    }
+
+    /// Distance from the origin to the centers of the outermost bricks.
+    pub fn half_extent(&self) -> Vec2 {
+        Vec2::new((self.columns - 1) as f32 / 2., (self.rows - 1) as f32 / 2.) * self.brick_size
+    }
+}
+
+/// What a ball bounced off.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Surface {
+    /// Index into `RegionWorld::walls`, followed by the barriers.
+    Wall(usize),
+    /// A board cell that blocks the ball, captured if it is capturable.
+    Brick(usize),
+    /// Index into `RegionWorld::balls`, of another team.
+    Ball(usize),
+}
+
+/// Something that happened during a step, for views to react to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WorldEvent {
+    /// `ball` took `cell` from its owner, or from nobody.
+    Captured {
+        cell: usize,
+        from: Cell,
+        to: Cell,
+        ball: usize,
+    },
+    Bounced {
+        ball: usize,
+        surface: Surface,
+    },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Hit {
+    // fraction of the motion travelled before the contact
+    time: f32,
+    normal: Vec2,
+    surface: Surface,
+}
+
+// How many surfaces a ball may hit within one tick. Whatever motion is left after
+// that is dropped, which only happens when a ball is wedged into a corner.
+const MAX_BOUNCES: usize = 8;
+// Contacts closer together than this, as a fraction of the motion, happen at the
+// same instant and are resolved together.
+const SIMULTANEOUS: f32 = 1e-4;
+// Extra distance a ball is pushed out of a surface it overlaps.
+const SKIN: f32 = 1e-3;
+
+/// Folds the normals of simultaneous contacts into one reflection normal.
+///
+/// Every normal the velocity still points into is reflected in turn, so two bricks
+/// side by side flip the ball once instead of cancelling out, and a wall and a
+/// brick meeting in a corner send it back out of the corner. Returns `None` when
+/// the ball is already moving away from every contact.
+pub fn merged_normal(velocity: Vec2, normals: impl IntoIterator<Item = Vec2>) -> Option<Vec2> {
+    let mut reflected = velocity;
+    for normal in normals {
+        let approach = reflected.dot(normal);
+        if approach < 0. {
+            reflected -= 2. * approach * normal;
+        }
+    }
+    // the reflections keep the speed, so a single mirror maps `velocity` onto
+    // `reflected`: the one across their difference
+    (velocity - reflected).try_normalize()
+}
+
+/// Sweeps a circle of `radius` from `center` by `motion` against `aabb`.
+///
+/// Returns the fraction of `motion` travelled before the first contact and the
+/// surface normal at that point, or `None` if the circle misses the box or is
+/// already moving away from it.
+pub fn sweep_circle_aabb(
+    center: Vec2,
+    radius: f32,
+    motion: Vec2,
+    aabb: Aabb2d,
+) -> Option<(f32, Vec2)> {
+    let closest = aabb.closest_point(center);
+    if center.distance_squared(closest) < radius * radius {
+        let normal = overlap_normal(center, closest, aabb);
+        return (motion.dot(normal) < 0.).then_some((0., normal));
+    }
+
+    // slab test against the box grown by the radius
+    let grown = Aabb2d {
+        min: aabb.min - radius,
+        max: aabb.max + radius,
+    };
+    let mut entry = f32::NEG_INFINITY;
+    let mut exit = f32::INFINITY;
+    let mut normal = Vec2::ZERO;
+    for axis in 0..2 {
+        if motion[axis].abs() <= f32::EPSILON {
+            if center[axis] < grown.min[axis] || center[axis] > grown.max[axis] {
+                return None;
+            }
+            continue;
+        }
+        let (near, far) = if motion[axis] > 0. {
+            (grown.min[axis], grown.max[axis])
+        } else {
+            (grown.max[axis], grown.min[axis])
+        };
+        let near = (near - center[axis]) / motion[axis];
+        let far = (far - center[axis]) / motion[axis];
+        if near > entry {
+            entry = near;
+            normal = Vec2::ZERO;
+            normal[axis] = -motion[axis].signum();
+        }
+        exit = exit.min(far);
+    }
+    if entry > exit || entry > 1. || exit <= 0. {
+        return None;
+    }
+
+    let contact = center + motion * entry.max(0.);
+    let outside_x = contact.x < aabb.min.x || contact.x > aabb.max.x;
+    let outside_y = contact.y < aabb.min.y || contact.y > aabb.max.y;
+    // starting inside the grown box without touching the real one is only
+    // possible next to a corner
+    if entry < 0. || (outside_x && outside_y) {
+        // the grown box really has rounded corners, so test the corner itself
+        let corner = contact.clamp(aabb.min, aabb.max);
+        return sweep_circle_point(center, radius, motion, corner);
+    }
+    (motion.dot(normal) < 0.).then_some((entry, normal))
+}
+
+fn sweep_circle_point(center: Vec2, radius: f32, motion: Vec2, point: Vec2) -> Option<(f32, Vec2)> {
+    let offset = center - point;
+    let a = motion.length_squared();
+    let b = offset.dot(motion);
+    let c = offset.length_squared() - radius * radius;
+    let discriminant = b * b - a * c;
+    // moving away from the point, or passing it by
+    if a <= f32::EPSILON || b >= 0. || discriminant < 0. {
+        return None;
+    }
+    let time = ((-b - discriminant.sqrt()) / a).max(0.);
+    if time > 1. {
+        return None;
+    }
+    Some((time, (offset + motion * time).normalize_or_zero()))
+}
+
+// How far a circle overlapping `aabb` has to move to clear it, or `None` if it
+// does not overlap
+fn penetration(center: Vec2, radius: f32, aabb: Aabb2d) -> Option<Vec2> {
+    let closest = aabb.closest_point(center);
+    let distance = center.distance(closest);
+    if distance >= radius {
+        return None;
+    }
+    let normal = overlap_normal(center, closest, aabb);
+    let depth = if distance > 0. {
+        radius - distance
+    } else {
+        // the center is inside, it has to clear the side as well as the radius
+        let inside = (center - aabb.min).min(aabb.max - center).min_element();
+        radius + inside
+    };
+    Some(normal * (depth + SKIN))
+}
+
+// Direction to push a circle that already overlaps `aabb` out of it
+fn overlap_normal(center: Vec2, closest: Vec2, aabb: Aabb2d) -> Vec2 {
+    if let Some(normal) = (center - closest).try_normalize() {
+        return normal;
+    }
+    // the center is inside the box, leave through the nearest side
+    [
+        (center.x - aabb.min.x, Vec2::NEG_X),
+        (aabb.max.x - center.x, Vec2::X),
+        (center.y - aabb.min.y, Vec2::NEG_Y),
+        (aabb.max.y - center.y, Vec2::Y),
+    ]
+    .into_iter()
+    .min_by(|a, b| a.0.total_cmp(&b.0))
+    .map(|(_, normal)| normal)
+    .unwrap_or(Vec2::Y)
+}
+
+// The ball-ball collision rules from `MatchSettings`
+#[derive(Debug, Clone, Copy)]
+struct BallContact {
+    restitution: f32,
+    steals: bool,
+}
+
+#[derive(Debug, Clone)]
+pub struct RegionWorld {
+    grid: Grid,
+    cells: Vec<Cell>,
+    // cells a team can own, the board is won by owning all of them
+    capturable: usize,
+    walls: Vec<Aabb2d>,
+    balls: Vec<Ball>,
+    // number of cells owned by each team, indexed by `TeamId`
+    scores: Vec<usize>,
+    // cells whose owner or armor changed since the last `drain_captures`
+    captures: Vec<usize>,
+    // captures and bounces since the last `drain_events`
+    events: Vec<WorldEvent>,
+    // scratch buffer for the contacts of one bounce, kept to avoid allocating
+    contacts: Vec<Hit>,
+    // continues the seeded stream that built the board, for pickups
+    rng: StdRng,
+    power_ups: Vec<PowerUpKind>,
+    pickups: Vec<Pickup>,
+    next_pickup: u32,
+    effects: Vec<Effect>,
+    // pickups collected since the last `drain_picked_up`
+    picked_up: Vec<PickedUp>,
+    // indexed by `TeamId`, empty unless players steer
+    steering: Vec<Steering>,
+    // how balls of different teams meet, `None` when they pass through
+    ball_contact: Option<BallContact>,
+    // XOR of `stalemate::cell_hash` over the board, kept up to date on every
+    // change
+    board_hash: u64,
+    stalemate_response: Option<StalemateResponse>,
+    detector: Detector,
+    // stalemates detected since the last `drain_stalemates`
+    stalemates: Vec<Stalemate>,
+    // set when a stalemate ends the match early
+    forced_outcome: Option<MatchOutcome>,
+    speed_curve: SpeedCurve,
+    // indexed by `TeamId`, how much faster than launch speed the team's balls
+    // move
+    paces: Vec<f32>,
+    barriers: Vec<Barrier>,
+    // indexed by `TeamId`, empty unless barriers are on
+    barrier_stock: Vec<BarrierStock>,
+    next_barrier: u32,
+    tick: u32,
+    tick_limit: Option<u32>,
+}
+
+impl RegionWorld {
+    pub fn from_cells(grid: Grid, cells: Vec<Cell>) -> Self {
+        assert_eq!(cells.len(), grid.len());
+        let mut scores = vec![0; TeamId::ALL.len()];
+        for owner in cells.iter().filter_map(Cell::owner) {
+            if scores.len() <= owner.0 as usize {
+                scores.resize(owner.0 as usize + 1, 0);
+            }
+            scores[owner.0 as usize] += 1;
+        }
+        let capturable = cells.iter().filter(|cell| cell.is_capturable()).count();
+        let paces = vec![1.; scores.len()];
+        let board_hash = cells.iter().enumerate().fold(0, |hash, (index, cell)| {
+            hash ^ stalemate::cell_hash(index, *cell)
+        });
+        Self {
+            grid,
+            cells,
+            capturable,
+            walls: Vec::new(),
+            balls: Vec::new(),
+            scores,
+            captures: Vec::new(),
+            events: Vec::new(),
+            contacts: Vec::new(),
+            rng: StdRng::seed_from_u64(0),
+            power_ups: Vec::new(),
+            pickups: Vec::new(),
+            next_pickup: 0,
+            effects: Vec::new(),
+            picked_up: Vec::new(),
+            steering: Vec::new(),
+            ball_contact: None,
+            board_hash,
+            stalemate_response: None,
+            detector: Detector::default(),
+            stalemates: Vec::new(),
+            forced_outcome: None,
+            speed_curve: SpeedCurve::default(),
+            paces,
+            barriers: Vec::new(),
+            barrier_stock: Vec::new(),
+            next_barrier: 0,
+            tick: 0,
+            tick_limit: None,
+        }
+    }
+
+    /// A walled-in board with the balls a quarter of the way in from each side.
+    /// The default settings give the classic match: 31x31, split down the middle,
+    /// one ball per team.
+    pub fn new(settings: &MatchSettings) -> Self {
+        if let Some(map) = &settings.map {
+            return Self::from_map(map, settings);
+        }
+        let grid = Grid {
+            columns: settings.board_size,
+            rows: settings.board_size,
+            brick_size: BRICK_WIDTH,
+        };
+        let mut rng = StdRng::seed_from_u64(settings.seed.unwrap_or_default());
+        let cells = settings.layout.generate(grid, &mut rng);
+        let mut world = Self::from_cells(grid, cells.into_iter().map(Cell::Team).collect());
+        world.add_arena_walls(WALL_THICKNESS);
+        world.tick_limit = settings.tick_limit;
+
+        let start_x = grid.half_extent().x - (grid.columns / 4) as f32 * grid.brick_size;
+        let height = grid.half_extent().y * 2.;
+        let count = settings.balls_per_team;
+        // balls of a team are spread evenly along their starting column
+        for index in 0..count {
+            let y = height * (index + 1) as f32 / (count + 1) as f32 - height / 2.;
+            for (team, side, speed) in [
+                (TeamId::RED, -1., settings.ball_speeds[0]),
+                (TeamId::BLUE, 1., settings.ball_speeds[1]),
+            ] {
+                let velocity = match settings.seed {
+                    Some(_) => launch_velocity(&mut rng, speed),
+                    None => Vec2::splat(-side * speed),
+                };
+                let position = world.spawn_point(team, Vec2::new(side * start_x, y));
+                world.add_ball(Ball::new(team, position, velocity));
+            }
+        }
+        world.rng = rng;
+        world.apply_settings(settings);
+        world
+    }
+
+    fn from_map(map: &RegionMap, settings: &MatchSettings) -> Self {
+        let grid = Grid {
+            columns: map.columns,
+            rows: map.rows,
+            brick_size: BRICK_WIDTH,
+        };
+        let mut world = Self::from_cells(grid, map.cells.clone());
+        // the board edge is solid even where the map has no `#`
+        world.add_boundary_walls(WALL_THICKNESS);
+        world.tick_limit = settings.tick_limit;
+
+        let mut rng = StdRng::seed_from_u64(settings.seed.unwrap_or_default());
+        for (team, cell) in &map.spawns {
+            let speed = settings.ball_speeds[(team.0 as usize).min(1)];
+            let velocity = match settings.seed {
+                Some(_) => launch_velocity(&mut rng, speed),
+                None if *team == TeamId::RED => Vec2::splat(speed),
+                None => Vec2::splat(-speed),
+            };
+            world.add_ball(Ball::new(*team, grid.center(*cell), velocity));
+        }
+        world.rng = rng;
+        world.apply_settings(settings);
+        world
+    }
+
+    // Rules shared by generated boards and maps
+    fn apply_settings(&mut self, settings: &MatchSettings) {
+        self.power_ups = settings.power_ups.clone();
+        if settings.steering {
+            self.steering = vec![Steering::new(settings.boost); self.scores.len()];
+        }
+        self.ball_contact = settings.ball_collisions.then_some(BallContact {
+            restitution: settings.restitution,
+            steals: settings.contact_steals,
+        });
+        self.stalemate_response = settings.stalemate;
+        self.speed_curve = settings.speed_curve;
+        if settings.barriers {
+            self.barrier_stock = vec![BarrierStock::default(); self.scores.len()];
+        }
+    }
+
+    // `preferred` if the team owns the cell there, otherwise the center of the
+    // closest cell it does own, so no ball starts buried in enemy bricks
+    fn spawn_point(&self, team: TeamId, preferred: Vec2) -> Vec2 {
+        if self
+            .grid
+            .cell_at(preferred)
+            .is_some_and(|cell| self.cells[cell] == Cell::Team(team))
+        {
+            return preferred;
+        }
+        (0..self.cells.len())
+            .filter(|cell| self.cells[*cell] == Cell::Team(team))
+            .map(|cell| self.grid.center(cell))
+            .min_by(|a, b| {
+                a.distance_squared(preferred)
+                    .total_cmp(&b.distance_squared(preferred))
+            })
+            .unwrap_or(preferred)
+    }
+
+    pub fn add_arena_walls(&mut self, thickness: f32) {
+        let half_extent = self.grid.half_extent();
+        self.walls.extend(
+            WallLocation::ALL
+                .iter()
+                .map(|location| location.aabb(half_extent, thickness)),
+        );
+    }
+
+    /// Walls hugging the outside of the board, for arenas whose outermost cells
+    /// are playable.
+    pub fn add_boundary_walls(&mut self, thickness: f32) {
+        let grid_half =
+            Vec2::new(self.grid.columns as f32, self.grid.rows as f32) * self.grid.brick_size / 2.;
+        let half_extent = grid_half + thickness / 2.;
+        self.walls.extend(
+            WallLocation::ALL
+                .iter()
+                .map(|location| location.aabb(half_extent, thickness)),
+        );
+    }
+
+    pub fn add_ball(&mut self, ball: Ball) -> usize {
+        let team = ball.team.0 as usize;
+        if self.scores.len() <= team {
+            self.scores.resize(team + 1, 0);
+        }
+        self.balls.push(ball);
+        self.balls.len() - 1
+    }
+
+    pub fn grid(&self) -> Grid {
+        self.grid
+    }
+
+    pub fn cells(&self) -> &[Cell] {
+        &self.cells
+    }
+
+    pub fn walls(&self) -> &[Aabb2d] {
+        &self.walls
+    }
+
+    pub fn balls(&self) -> &[Ball] {
+        &self.balls
+    }
+
+    /// Cells a team can own, the board is won by owning all of them.
+    pub fn capturable(&self) -> usize {
+        self.capturable
+    }
+
+    pub fn count(&self, team: TeamId) -> usize {
+        self.scores.get(team.0 as usize).copied().unwrap_or(0)
+    }
+
+    pub fn tick(&self) -> u32 {
+        self.tick
+    }
+
+    /// `Some` once a team owns the whole board or the tick limit is reached.
+    pub fn outcome(&self) -> Option<MatchOutcome> {
+        if self.forced_outcome.is_some() {
+            return self.forced_outcome;
+        }
+        if let Some(team) = TeamId::ALL
+            .into_iter()
+            .find(|team| self.count(*team) == self.capturable)
+        {
+            return Some(MatchOutcome::Winner(team));
+        }
+        if self.tick_limit.is_some_and(|limit| self.tick >= limit) {
+            let [red, blue] = TeamId::ALL.map(|team| self.count(team));
+            return Some(match red.cmp(&blue) {
+                std::cmp::Ordering::Greater => MatchOutcome::Winner(TeamId::RED),
+                std::cmp::Ordering::Less => MatchOutcome::Winner(TeamId::BLUE),
+                std::cmp::Ordering::Equal => MatchOutcome::Draw,
+            });
+        }
+        None
+    }
+
+    pub fn drain_captures(&mut self) -> std::vec::Drain<'_, usize> {
+        self.captures.drain(..)
+    }
+
+    /// Cells changed since the last `drain_captures`, possibly more than once.
+    pub fn captures(&self) -> &[usize] {
+        &self.captures
+    }
+
+    /// Reports every cell on the next `drain_captures`, for views that have to
+    /// redraw the whole board.
+    pub fn touch_all_cells(&mut self) {
+        self.captures.clear();
+        self.captures.extend(0..self.cells.len());
+    }
+
+    /// Drops the captures and events nobody has read, for loops without a view
+    /// draining them.
+    pub fn clear_changes(&mut self) {
+        self.captures.clear();
+        self.events.clear();
+    }
+
+    pub fn drain_events(&mut self) -> std::vec::Drain<'_, WorldEvent> {
+        self.events.drain(..)
+    }
+
+    pub fn drain_stalemates(&mut self) -> std::vec::Drain<'_, Stalemate> {
+        self.stalemates.drain(..)
+    }
+
+    pub fn pickups(&self) -> &[Pickup] {
+        &self.pickups
+    }
+
+    pub fn effects(&self) -> &[Effect] {
+        &self.effects
+    }
+
+    pub fn drain_picked_up(&mut self) -> std::vec::Drain<'_, PickedUp> {
+        self.picked_up.drain(..)
+    }
+
+    /// Sets what the team's player is steering towards. Ignored unless the match
+    /// was started with `MatchSettings::steering`.
+    pub fn steer(&mut self, team: TeamId, input: SteeringInput) {
+        if let Some(steering) = self.steering.get_mut(team.0 as usize) {
+            steering.input = input;
+        }
+    }
+
+    /// Boost left for the team, from `0` to `1`, if it has a boost meter.
+    pub fn boost_meter(&self, team: TeamId) -> Option<f32> {
+        self.steering.get(team.0 as usize)?.meter
+    }
+
+    /// Pace of the team's balls from the speed curve, `1` at launch speed.
+    pub fn speed(&self, team: TeamId) -> f32 {
+        let pace = self.paces.get(team.0 as usize).copied().unwrap_or(1.);
+        if self.is_sudden_death() {
+            pace * SUDDEN_DEATH_SPEED
+        } else {
+            pace
+        }
+    }
+
+    /// Ticks until the tick limit ends the match, if it has one.
+    pub fn ticks_left(&self) -> Option<u32> {
+        Some(self.tick_limit?.saturating_sub(self.tick))
+    }
+
+    pub fn is_sudden_death(&self) -> bool {
+        self.speed_curve.is_sudden_death(self.tick, self.tick_limit)
+    }
+
+    pub fn barriers(&self) -> &[Barrier] {
+        &self.barriers
+    }
+
+    /// Barriers the team has left, `None` unless barriers are on.
+    pub fn barrier_stock(&self, team: TeamId) -> Option<BarrierStock> {
+        self.barrier_stock.get(team.0 as usize).copied()
+    }
+
+    /// Places a barrier for `team` centered on `center`. Returns `false` without
+    /// placing anything when barriers are off, the team is out of charges or
+    /// still cooling down, or `center` is off the board.
+    pub fn place_barrier(&mut self, team: TeamId, center: Vec2, vertical: bool) -> bool {
+        if center.abs().cmpgt(self.grid.half_extent()).any() {
+            return false;
+        }
+        let Some(stock) = self.barrier_stock.get_mut(team.0 as usize) else {
+            return false;
+        };
+        if stock.charges == 0 || stock.cooldown > 0 {
+            return false;
+        }
+        stock.charges -= 1;
+        stock.cooldown = BARRIER_COOLDOWN;
+        self.barriers
+            .push(Barrier::new(self.next_barrier, team, center, vertical));
+        self.next_barrier += 1;
+        true
+    }
+
+    /// Whether the team's cells can not be captured right now.
+    pub fn is_shielded(&self, team: TeamId) -> bool {
+        self.effects
+            .iter()
+            .any(|effect| effect.kind == PowerUpKind::Shield && effect.team == team)
+    }
+
+    /// Advances every ball by `dt` seconds. Each ball stops at every enemy brick
+    /// and wall it hits on the way, captures or bounces, and carries on with the
+    /// rest of its motion, so fast balls can not tunnel through anything.
+    pub fn step(&mut self, dt: f32) {
+        self.tick += 1;
+        self.expire_effects();
+        self.expire_barriers();
+        self.spawn_pickup();
+        for steering in &mut self.steering {
+            steering.update(dt);
+        }
+        for pace in &mut self.paces {
+            *pace = self.speed_curve.accelerate(*pace, 0, dt);
+        }
+        // balls split off during this tick start moving on the next one
+        for index in 0..self.balls.len() {
+            // the speed curve and boosting cover more ground per tick instead
+            // of changing the velocity
+            let mut ball_dt = dt * self.speed(self.balls[index].team) * self.ball_pace(index);
+            if let Some(steering) = self.steering.get(self.balls[index].team.0 as usize) {
+                let ball = &mut self.balls[index];
+                ball.velocity = steering::steer(ball.velocity, steering.input.direction, dt);
+                ball_dt *= steering.pace;
+            }
+            self.advance(index, ball_dt);
+            self.collect_pickups(index);
+        }
+        if let Some(contact) = self.ball_contact {
+            self.collide_balls(contact);
+        }
+        self.check_stalemate();
+    }
+
+    fn check_stalemate(&mut self) {
+        let Some(response) = self.stalemate_response else {
+            return;
+        };
+        let Some(kind) = self.detector.check(
+            self.tick,
+            &self.balls,
+            self.board_hash,
+            &self.scores,
+            self.capturable,
+        ) else {
+            return;
+        };
+        match response {
+            StalemateResponse::Jitter => {
+                for ball in &mut self.balls {
+                    let angle = self.rng.gen_range(JITTER_ANGLE.clone());
+                    let sign = if self.rng.gen() { 1. } else { -1. };
+                    ball.velocity = Vec2::from_angle(angle * sign).rotate(ball.velocity);
+                }
+            }
+            StalemateResponse::SpeedUp => {
+                for pace in &mut self.paces {
+                    *pace = self.speed_curve.speed_up(*pace, STALEMATE_SPEED_UP);
+                }
+            }
+            StalemateResponse::Draw => self.forced_outcome = Some(MatchOutcome::Draw),
+        }
+        self.detector.reset(self.tick, &self.scores);
+        self.stalemates.push(Stalemate {
+            tick: self.tick,
+            kind,
+            response,
+        });
+    }
+
+    // Checks every pair of balls from different teams, cheap for the few dozen
+    // balls a match has
+    fn collide_balls(&mut self, contact: BallContact) {
+        for second in 1..self.balls.len() {
+            for first in 0..second {
+                let (head, tail) = self.balls.split_at_mut(second);
+                let (a, b) = (&mut head[first], &mut tail[0]);
+                if a.team == b.team {
+                    continue;
+                }
+                // momentum along the line between the balls, before it changes
+                let normal = (b.position - a.position).normalize_or_zero();
+                let push_a = a.mass() * a.velocity.dot(normal);
+                let push_b = -b.mass() * b.velocity.dot(normal);
+                let (team_a, team_b) = (a.team, b.team);
+                let Some(point) = collide_balls(a, b, contact.restitution) else {
+                    continue;
+                };
+                self.events.push(WorldEvent::Bounced {
+                    ball: first,
+                    surface: Surface::Ball(second),
+                });
+                self.events.push(WorldEvent::Bounced {
+                    ball: second,
+                    surface: Surface::Ball(first),
+                });
+                if contact.steals {
+                    let (winner, team) = if push_a >= push_b {
+                        (first, team_a)
+                    } else {
+                        (second, team_b)
+                    };
+                    if let Some(cell) = self.grid.cell_at(point) {
+                        if self.cells[cell].capturable_by(team) && !self.is_protected(cell) {
+                            self.capture(cell, winner);
+                        }
+                    }
+                }
+                self.push_out(first);
+                self.push_out(second);
+            }
+        }
+    }
+
+    fn advance(&mut self, index: usize, dt: f32) {
+        let mut remaining = dt;
+        let mut contacts = std::mem::take(&mut self.contacts);
+        for _ in 0..MAX_BOUNCES {
+            let motion = self.balls[index].velocity * remaining;
+            let Some(time) = self.first_contacts(&self.balls[index], motion, &mut contacts) else {
+                self.balls[index].position += motion;
+                break;
+            };
+            self.balls[index].position += motion * time;
+            self.resolve_contacts(index, &contacts);
+            self.push_out(index);
+            remaining *= 1. - time;
+        }
+        self.contacts = contacts;
+    }
+
+    // The arena walls followed by the barriers, balls treat both the same
+    fn solid_walls(&self) -> impl Iterator<Item = Aabb2d> + '_ {
+        self.walls
+            .iter()
+            .copied()
+            .chain(self.barriers.iter().map(|barrier| barrier.aabb))
+    }
+
+    // Fills `contacts` with every surface the ball reaches first along `motion` and
+    // returns the time of that contact
+    fn first_contacts(&self, ball: &Ball, motion: Vec2, contacts: &mut Vec<Hit>) -> Option<f32> {
+        contacts.clear();
+        let start = ball.bounding_circle().aabb_2d();
+        let end = BoundingCircle::new(ball.position + motion, ball.radius).aabb_2d();
+        let bricks = self
+            .grid
+            .cells_overlapping(start.merge(&end))
+            .filter(|cell| self.cells[*cell].blocks(ball.team))
+            .map(|cell| (Surface::Brick(cell), self.grid.aabb(cell)));
+        let walls = self
+            .solid_walls()
+            .enumerate()
+            .map(|(wall, aabb)| (Surface::Wall(wall), aabb));
+        contacts.extend(bricks.chain(walls).filter_map(|(surface, aabb)| {
+            let (time, normal) = sweep_circle_aabb(ball.position, ball.radius, motion, aabb)?;
+            Some(Hit {
+                time,
+                normal,
+                surface,
+            })
+        }));
+
+        let first = contacts
+            .iter()
+            .map(|hit| hit.time)
+            .min_by(|a, b| a.total_cmp(b))?;
+        contacts.retain(|hit| hit.time <= first + SIMULTANEOUS);
+        Some(first)
+    }
+
+    // Hits every brick in `contacts` and bounces the ball once off all of them
+    fn resolve_contacts(&mut self, index: usize, contacts: &[Hit]) {
+        for hit in contacts {
+            if let Surface::Brick(cell) = hit.surface {
+                self.hit_brick(cell, index);
+            }
+        }
+        let ball = &mut self.balls[index];
+        if let Some(normal) = merged_normal(ball.velocity, contacts.iter().map(|hit| hit.normal)) {
+            ball.reflect(normal);
+            // simultaneous contacts make a single bounce
+            if let Some(hit) = contacts.first() {
+                self.events.push(WorldEvent::Bounced {
+                    ball: index,
+                    surface: hit.surface,
+                });
+            }
+        }
+    }
+
+    // Positional correction: moves the ball out of every wall and enemy brick it
+    // still overlaps, so it can not sink into a surface and get stuck. Pushes along
+    // the same direction are not added up, only the deepest one counts.
+    fn push_out(&mut self, index: usize) {
+        let ball = &self.balls[index];
+        let bricks = self
+            .grid
+            .cells_overlapping(ball.bounding_circle().aabb_2d())
+            .filter(|cell| self.cells[*cell].blocks(ball.team))
+            .map(|cell| self.grid.aabb(cell));
+        let mut most_positive = Vec2::ZERO;
+        let mut most_negative = Vec2::ZERO;
+        for aabb in bricks.chain(self.solid_walls()) {
+            if let Some(push) = penetration(ball.position, ball.radius, aabb) {
+                most_positive = most_positive.max(push);
+                most_negative = most_negative.min(push);
+            }
+        }
+        self.balls[index].position += most_positive + most_negative;
+    }
+
+    // Armored bricks lose a hit point, everything else capturable changes hands
+    fn hit_brick(&mut self, cell: usize, ball: usize) {
+        if !self.cells[cell].capturable_by(self.balls[ball].team) {
+            return;
+        }
+        if self.is_protected(cell) {
+            return;
+        }
+        if let Cell::Armored { owner, hits } = self.cells[cell] {
+            if hits > 1 {
+                self.set_cell(
+                    cell,
+                    Cell::Armored {
+                        owner,
+                        hits: hits - 1,
+                    },
+                );
+                return;
+            }
+        }
+        self.capture(cell, ball);
+    }
+
+    fn capture(&mut self, cell: usize, ball: usize) {
+        let team = self.balls[ball].team;
+        self.events.push(WorldEvent::Captured {
+            cell,
+            from: self.cells[cell],
+            to: Cell::Team(team),
+            ball,
+        });
+        if let Some(owner) = self.cells[cell].owner() {
+            self.scores[owner.0 as usize] -= 1;
+        }
+        self.scores[team.0 as usize] += 1;
+        if let Some(pace) = self.paces.get_mut(team.0 as usize) {
+            *pace = self.speed_curve.accelerate(*pace, 1, 0.);
+        }
+        self.set_cell(cell, Cell::Team(team));
+    }
+
+    fn set_cell(&mut self, cell: usize, state: Cell) {
+        self.board_hash ^=
+            stalemate::cell_hash(cell, self.cells[cell]) ^ stalemate::cell_hash(cell, state);
+        self.cells[cell] = state;
+        self.captures.push(cell);
+    }
+
+    // Cells of a shielded team bounce balls but can not be captured or damaged
+    fn is_protected(&self, cell: usize) -> bool {
+        self.cells[cell]
+            .owner()
+            .is_some_and(|owner| self.is_shielded(owner))
+    }
+
+    // Every `PICKUP_INTERVAL` ticks a random enabled power-up appears on a random
+    // capturable cell that has none yet
+    fn spawn_pickup(&mut self) {
+        if self.power_ups.is_empty()
+            || !self.tick.is_multiple_of(PICKUP_INTERVAL)
+            || self.pickups.len() >= MAX_PICKUPS
+        {
+            return;
+        }
+        let kind = self.power_ups[self.rng.gen_range(0..self.power_ups.len())];
+        // a few tries are plenty, the board is mostly capturable
+        for _ in 0..32 {
+            let cell = self.rng.gen_range(0..self.cells.len());
+            if self.cells[cell].is_capturable()
+                && self.pickups.iter().all(|pickup| pickup.cell != cell)
+            {
+                self.pickups.push(Pickup {
+                    id: self.next_pickup,
+                    kind,
+                    cell,
+                    position: self.grid.center(cell),
+                });
+                self.next_pickup += 1;
+                return;
+            }
+        }
+    }
+
+    fn collect_pickups(&mut self, index: usize) {
+        let mut pickup = 0;
+        while pickup < self.pickups.len() {
+            let ball = &self.balls[index];
+            let reach = ball.radius + PICKUP_RADIUS;
+            if ball
+                .position
+                .distance_squared(self.pickups[pickup].position)
+                < reach * reach
+            {
+                let Pickup {
+                    kind,
+                    cell,
+                    position,
+                    ..
+                } = self.pickups.swap_remove(pickup);
+                self.apply_power_up(kind, index, cell);
+                self.picked_up.push(PickedUp {
+                    kind,
+                    team: self.balls[index].team,
+                    position,
+                });
+            } else {
+                pickup += 1;
+            }
+        }
+    }
+
+    fn apply_power_up(&mut self, kind: PowerUpKind, index: usize, cell: usize) {
+        let team = self.balls[index].team;
+        let ball = match kind {
+            PowerUpKind::SpeedBoost | PowerUpKind::Growth => Some(index),
+            PowerUpKind::Shield => None,
+            PowerUpKind::MultiBall => {
+                self.split_ball(index);
+                return;
+            }
+            PowerUpKind::PaintBomb => {
+                self.paint_bomb(cell, index);
+                return;
+            }
+        };
+        let ticks_left = kind.duration().unwrap_or_default();
+        // picking up a running effect again only refreshes its timer
+        if let Some(effect) = self
+            .effects
+            .iter_mut()
+            .find(|effect| effect.kind == kind && effect.team == team && effect.ball == ball)
+        {
+            effect.ticks_left = ticks_left;
+            return;
+        }
+        if kind == PowerUpKind::Growth {
+            self.balls[index].radius *= GROWTH;
+            self.push_out(index);
+        }
+        self.effects.push(Effect {
+            kind,
+            team,
+            ball,
+            ticks_left,
+        });
+    }
+
+    fn expire_barriers(&mut self) {
+        for stock in &mut self.barrier_stock {
+            stock.cooldown = stock.cooldown.saturating_sub(1);
+        }
+        for barrier in &mut self.barriers {
+            barrier.ticks_left -= 1;
+        }
+        self.barriers.retain(|barrier| barrier.ticks_left > 0);
+    }
+
+    fn expire_effects(&mut self) {
+        let mut effect = 0;
+        while effect < self.effects.len() {
+            self.effects[effect].ticks_left = self.effects[effect].ticks_left.saturating_sub(1);
+            if self.effects[effect].ticks_left > 0 {
+                effect += 1;
+                continue;
+            }
+            let Effect { kind, ball, .. } = self.effects.swap_remove(effect);
+            if let (PowerUpKind::Growth, Some(ball)) = (kind, ball) {
+                self.balls[ball].radius /= GROWTH;
+            }
+        }
+    }
+
+    // Pace of a single ball from its power-ups, on top of its team's pace. Like
+    // the team's pace it scales how far the ball moves, never its velocity.
+    fn ball_pace(&self, index: usize) -> f32 {
+        let boosted = self
+            .effects
+            .iter()
+            .any(|effect| effect.kind == PowerUpKind::SpeedBoost && effect.ball == Some(index));
+        if boosted {
+            SPEED_BOOST
+        } else {
+            1.
+        }
+    }
+
+    // The two halves leave at an angle to the original direction. The new ball
+    // starts without the effects of the old one.
+    fn split_ball(&mut self, index: usize) {
+        let velocity = self.balls[index].velocity;
+        let ball = &mut self.balls[index];
+        ball.velocity = Vec2::from_angle(-SPLIT_ANGLE).rotate(ball.velocity);
+        let split = Ball::new(
+            ball.team,
+            ball.position,
+            Vec2::from_angle(SPLIT_ANGLE).rotate(velocity),
+        );
+        let split = self.add_ball(split);
+        self.push_out(split);
+    }
+
+    fn paint_bomb(&mut self, center: usize, ball: usize) {
+        let team = self.balls[ball].team;
+        let (column, row) = self.grid.coords(center);
+        for y in row.saturating_sub(1)..=(row + 1).min(self.grid.rows - 1) {
+            for x in column.saturating_sub(1)..=(column + 1).min(self.grid.columns - 1) {
+                let cell = y * self.grid.columns + x;
+                if self.cells[cell].capturable_by(team) && !self.is_protected(cell) {
+                    self.capture(cell, ball);
+                }
+            }
+        }
+    }
+}
+
+// A random diagonal-ish direction. Angles close to the axes are avoided so a ball
+// never starts out shuttling along a single row or column.
+fn launch_velocity(rng: &mut StdRng, speed: f32) -> Vec2 {
+    let quadrant = rng.gen_range(0..4) as f32 * FRAC_PI_2;
+    let angle = rng.gen_range(20f32..70.).to_radians();
+    Vec2::from_angle(quadrant + angle) * speed
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 11x11 bricks of 20, so cell (5, 5) is centered on the origin
+    const GRID: Grid = Grid {
+        columns: 11,
+        rows: 11,
+        brick_size: 20.,
+    };
+
+    fn board(grid: Grid, owner: impl Fn(usize, usize) -> TeamId) -> RegionWorld {
+        let cells = (0..grid.len())
+            .map(|cell| {
+                let (column, row) = grid.coords(cell);
+                Cell::Team(owner(column, row))
+            })
+            .collect();
+        RegionWorld::from_cells(grid, cells)
+    }
+
+    fn bounces(world: &mut RegionWorld) -> usize {
+        world
+            .drain_events()
+            .filter(|event| matches!(event, WorldEvent::Bounced { .. }))
+            .count()
+    }
+
+    fn assert_outside(world: &RegionWorld, ball: &Ball) {
+        for (cell, state) in world.cells().iter().enumerate() {
+            if state.blocks(ball.team) {
+                let overlap = penetration(ball.position, ball.radius, GRID.aabb(cell));
+                assert_eq!(overlap, None, "{ball:?} overlaps brick {cell}");
+            }
+        }
+        for wall in world.walls() {
+            let overlap = penetration(ball.position, ball.radius, *wall);
+            assert_eq!(overlap, None, "{ball:?} overlaps wall {wall:?}");
+        }
+    }
+
+    #[test]
+    fn step_moves_balls_through_their_own_bricks() {
+        let mut world = board(GRID, |_, _| TeamId::RED);
+        world.add_ball(Ball::new(TeamId::RED, Vec2::ZERO, Vec2::new(100., 50.)));
+        world.step(0.1);
+
+        assert_eq!(world.balls()[0].position, Vec2::new(10., 5.));
+        assert_eq!(world.balls()[0].velocity, Vec2::new(100., 50.));
+        assert_eq!(world.drain_captures().count(), 0);
+        assert_eq!(bounces(&mut world), 0);
+    }
+
+    #[test]
+    fn capture_moves_the_cell_between_scores() {
+        let mut world = board(GRID, |column, _| {
+            if column >= 6 {
+                TeamId::BLUE
+            } else {
+                TeamId::RED
+            }
+        });
+        // the blue bricks start at 10
+        world.add_ball(Ball::new(
+            TeamId::RED,
+            Vec2::new(-5., 0.),
+            Vec2::new(100., 0.),
+        ));
+        world.step(0.1);
+
+        // column 6 of the middle row
+        let cell = 5 * 11 + 6;
+        assert_eq!(world.cells()[cell], Cell::Team(TeamId::RED));
+        assert_eq!(world.drain_captures().collect::<Vec<_>>(), [cell]);
+        assert_eq!(world.count(TeamId::RED), 66 + 1);
+        assert_eq!(world.count(TeamId::BLUE), 55 - 1);
+        assert!(world.balls()[0].velocity.x < 0.);
+        let captured: Vec<_> = world
+            .drain_events()
+            .filter(|event| matches!(event, WorldEvent::Captured { .. }))
+            .collect();
+        assert_eq!(
+            captured,
+            [WorldEvent::Captured {
+                cell,
+                from: Cell::Team(TeamId::BLUE),
+                to: Cell::Team(TeamId::RED),
+                ball: 0,
+            }]
+        );
+    }
+
+    #[test]
+    fn walls_bounce_without_capturing() {
+        let mut world = board(GRID, |_, _| TeamId::RED);
+        world.add_arena_walls(WALL_THICKNESS);
+        // the right wall's inner face is at 80
+        world.add_ball(Ball::new(
+            TeamId::RED,
+            Vec2::new(65., 0.),
+            Vec2::new(100., 0.),
+        ));
+        world.step(0.1);
+
+        assert!(world.balls()[0].velocity.x < 0.);
+        assert_eq!(world.count(TeamId::RED), GRID.len());
+        assert_eq!(world.drain_captures().count(), 0);
+        assert_eq!(bounces(&mut world), 1);
+    }
+
+    #[test]
+    fn owning_the_whole_board_wins() {
+        let mut world = board(GRID, |column, row| {
+            if (column, row) == (5, 5) {
+                TeamId::BLUE
+            } else {
+                TeamId::RED
+            }
+        });
+        world.add_ball(Ball::new(
+            TeamId::RED,
+            Vec2::new(-20., 0.),
+            Vec2::new(100., 0.),
+        ));
+        assert_eq!(world.outcome(), None);
+        world.step(0.1);
+
+        assert_eq!(world.count(TeamId::RED), GRID.len());
+        assert_eq!(world.outcome(), Some(MatchOutcome::Winner(TeamId::RED)));
+    }
+
+    #[test]
+    fn tick_limit_goes_to_the_bigger_team_or_a_draw() {
+        let mut world = board(GRID, |column, _| {
+            if column >= 5 {
+                TeamId::BLUE
+            } else {
+                TeamId::RED
+            }
+        });
+        world.tick_limit = Some(1);
+        assert_eq!(world.outcome(), None);
+        world.step(TICK_SECONDS);
+        assert_eq!(world.tick(), 1);
+        assert_eq!(world.outcome(), Some(MatchOutcome::Winner(TeamId::BLUE)));
+
+        // ten columns split down the middle
+        let grid = Grid {
+            columns: 10,
+            ..GRID
+        };
+        let mut world = board(grid, |column, _| {
+            if column >= 5 {
+                TeamId::BLUE
+            } else {
+                TeamId::RED
+            }
+        });
+        world.tick_limit = Some(1);
+        world.step(TICK_SECONDS);
+        assert_eq!(world.outcome(), Some(MatchOutcome::Draw));
+    }
+
+    #[test]
+    fn fast_balls_do_not_tunnel_through_a_thin_wall() {
+        // a one brick thick blue column from 50 to 70
+        let mut world = board(GRID, |column, _| {
+            if column == 8 {
+                TeamId::BLUE
+            } else {
+                TeamId::RED
+            }
+        });
+        // twenty times the normal speed over four ticks covers 125, far past the column
+        let dt = 4. * TICK_SECONDS;
+        world.add_ball(Ball::new(
+            TeamId::RED,
+            Vec2::new(-50., 3.),
+            Vec2::new(20. * BALL_SPEED, 0.),
+        ));
+        world.step(dt);
+
+        let ball = &world.balls()[0];
+        assert!(ball.velocity.x < 0., "{ball:?}");
+        assert!(ball.position.x + ball.radius <= 50. + 1e-3, "{ball:?}");
+        assert_eq!(world.count(TeamId::BLUE), 11 - 1);
+    }
+
+    #[test]
+    fn corner_hit_reflects_once() {
+        let mut world = board(GRID, |column, row| {
+            if (column, row) == (5, 5) {
+                TeamId::BLUE
+            } else {
+                TeamId::RED
+            }
+        });
+        // reaches the brick's bottom-left corner head on during the step
+        world.add_ball(Ball::new(
+            TeamId::RED,
+            Vec2::new(-25., -25.),
+            Vec2::new(100., 100.),
+        ));
+        world.step(0.1);
+
+        let ball = world.balls()[0].clone();
+        assert!(
+            (ball.velocity - Vec2::new(-100., -100.)).length() < 1e-3,
+            "{ball:?}"
+        );
+        assert_eq!(world.count(TeamId::BLUE), 0);
+        assert_eq!(bounces(&mut world), 1);
+        assert_outside(&world, &ball);
+    }
+
+    #[test]
+    fn double_brick_hit_reflects_once() {
+        let mut world = board(
+            GRID,
+            |_, row| if row >= 6 { TeamId::BLUE } else { TeamId::RED },
+        );
+        // straight up into the seam between two blue bricks, whose bottom is at 10
+        world.add_ball(Ball::new(
+            TeamId::RED,
+            Vec2::new(10., -5.),
+            Vec2::new(0., 200.),
+        ));
+        world.step(0.1);
+
+        let ball = world.balls()[0].clone();
+        assert_eq!(ball.velocity, Vec2::new(0., -200.));
+        assert_eq!(world.count(TeamId::RED), 66 + 2);
+        assert_eq!(bounces(&mut world), 1);
+        assert_outside(&world, &ball);
+    }
+
+    #[test]
+    fn wall_and_brick_hit_reflects_once() {
+        let mut world = board(
+            GRID,
+            |_, row| if row >= 6 { TeamId::BLUE } else { TeamId::RED },
+        );
+        world.add_arena_walls(WALL_THICKNESS);
+        // into the corner where the right wall, at 80, meets the blue bricks
+        world.add_ball(Ball::new(
+            TeamId::RED,
+            Vec2::new(60., -10.),
+            Vec2::new(100., 100.),
+        ));
+        world.step(0.125);
+
+        let ball = world.balls()[0].clone();
+        assert!(
+            (ball.velocity - Vec2::new(-100., -100.)).length() < 1e-3,
+            "{ball:?}"
+        );
+        assert_eq!(bounces(&mut world), 1);
+        assert_outside(&world, &ball);
+    }
+
+    #[test]
+    fn ball_collisions_and_stalemates_are_off_by_default() {
+        let settings = MatchSettings::default();
+        assert!(!settings.ball_collisions);
+        assert_eq!(settings.stalemate, None);
+        let world = RegionWorld::new(&settings);
+        assert!(world.ball_contact.is_none());
+        assert!(world.stalemate_response.is_none());
+    }
+
+    #[test]
+    fn stalemate_speed_up_stays_under_the_speed_curve_cap() {
+        // a lone ball on its own board never captures, so the match stalls
+        let mut world = board(GRID, |_, _| TeamId::RED);
+        world.add_arena_walls(WALL_THICKNESS);
+        world.add_ball(Ball::new(TeamId::RED, Vec2::ZERO, Vec2::new(100., 70.)));
+        world.apply_settings(&MatchSettings {
+            stalemate: Some(StalemateResponse::SpeedUp),
+            speed_curve: SpeedCurve {
+                max_speed: 1.5,
+                ..SpeedCurve::default()
+            },
+            ..MatchSettings::default()
+        });
+        for _ in 0..20_000 {
+            world.step(TICK_SECONDS);
+        }
+        assert!(world.drain_stalemates().count() > 1);
+        for team in TeamId::ALL {
+            assert_eq!(world.speed(team), 1.5);
+        }
+    }
+}
@@ -0,0 +1,54 @@
+// Short-lived wall segments placed by the players or spectators during a match.
+// Each team has a few charges and has to wait between placements. Balls collide
+// with barriers exactly like with the arena walls.
+use bevy::math::{bounding::Aabb2d, Vec2};
+
+use super::{TeamId, BRICK_WIDTH};
+
+pub const BARRIER_CHARGES: u32 = 5;
+// two seconds between placements and three seconds on the board
+pub const BARRIER_COOLDOWN: u32 = 64 * 2;
+pub const BARRIER_TICKS: u32 = 64 * 3;
+const BARRIER_LENGTH: f32 = BRICK_WIDTH * 3.;
+const BARRIER_THICKNESS: f32 = BRICK_WIDTH / 2.;
+
+#[derive(Debug, Clone, Copy)]
+pub struct Barrier {
+    /// Unique for the match, so renderers can tell barriers apart.
+    pub id: u32,
+    pub team: TeamId,
+    pub aabb: Aabb2d,
+    pub ticks_left: u32,
+}
+
+impl Barrier {
+    pub(super) fn new(id: u32, team: TeamId, center: Vec2, vertical: bool) -> Self {
+        let half_size = if vertical {
+            Vec2::new(BARRIER_THICKNESS, BARRIER_LENGTH) / 2.
+        } else {
+            Vec2::new(BARRIER_LENGTH, BARRIER_THICKNESS) / 2.
+        };
+        Self {
+            id,
+            team,
+            aabb: Aabb2d::new(center, half_size),
+            ticks_left: BARRIER_TICKS,
+        }
+    }
+}
+
+/// Charges a team has left and ticks until it may place the next barrier.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BarrierStock {
+    pub charges: u32,
+    pub cooldown: u32,
+}
+
+impl Default for BarrierStock {
+    fn default() -> Self {
+        Self {
+            charges: BARRIER_CHARGES,
+            cooldown: 0,
+        }
+    }
+}
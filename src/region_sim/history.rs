@@ -0,0 +1,195 @@
+// The last few seconds of a match, for instant replays: where every ball was on
+// every tick and which cells changed. Old ticks are dropped as new ones come
+// in, so once the buffers have grown to the busiest window they stop
+// allocating.
+use std::collections::VecDeque;
+
+use bevy::math::Vec2;
+
+use super::{Cell, RegionWorld, TeamId};
+
+/// Ticks the history reaches back, ten seconds at the default rate.
+pub const HISTORY_TICKS: usize = 64 * 10;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BallSample {
+    pub team: TeamId,
+    pub position: Vec2,
+    pub radius: f32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CellChange {
+    /// Tick whose step changed the cell.
+    pub tick: u32,
+    pub cell: usize,
+    pub before: Cell,
+    pub after: Cell,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Frame {
+    tick: u32,
+    // samples this frame added to `History::balls`
+    balls: usize,
+    counts: [usize; 2],
+}
+
+#[derive(Debug, Clone)]
+pub struct History {
+    // the board as of the last recorded tick, to know what changed cells were
+    board: Vec<Cell>,
+    frames: VecDeque<Frame>,
+    balls: VecDeque<BallSample>,
+    // only changes after the oldest frame, which already shows the older ones
+    changes: VecDeque<CellChange>,
+}
+
+impl History {
+    pub fn new(world: &RegionWorld) -> Self {
+        Self {
+            board: world.cells().to_vec(),
+            frames: VecDeque::with_capacity(HISTORY_TICKS),
+            balls: VecDeque::with_capacity(HISTORY_TICKS * world.balls().len()),
+            changes: VecDeque::new(),
+        }
+    }
+
+    /// Adds the tick `world` just stepped. Has to run before the captures are
+    /// drained.
+    pub fn record(&mut self, world: &RegionWorld) {
+        let tick = world.tick();
+        for &cell in world.captures() {
+            let after = world.cells()[cell];
+            let before = std::mem::replace(&mut self.board[cell], after);
+            if before != after {
+                self.changes.push_back(CellChange {
+                    tick,
+                    cell,
+                    before,
+                    after,
+                });
+            }
+        }
+
+        if self.frames.len() == HISTORY_TICKS {
+            if let Some(oldest) = self.frames.pop_front() {
+                self.balls.drain(..oldest.balls);
+            }
+        }
+        self.frames.push_back(Frame {
+            tick,
+            balls: world.balls().len(),
+            counts: TeamId::ALL.map(|team| world.count(team)),
+        });
+        self.balls
+            .extend(world.balls().iter().map(|ball| BallSample {
+                team: ball.team,
+                position: ball.position,
+                radius: ball.radius,
+            }));
+        let first = self.first_tick();
+        while self
+            .changes
+            .front()
+            .is_some_and(|change| change.tick <= first)
+        {
+            self.changes.pop_front();
+        }
+    }
+
+    /// Forgets everything before the current tick, which keeps its balls so a
+    /// replay starting there still shows them.
+    pub fn clear(&mut self) {
+        let last = self.frames.back().copied();
+        self.frames.clear();
+        self.changes.clear();
+        match last {
+            Some(last) => {
+                // the newest samples are the last frame's
+                self.balls.drain(..self.balls.len() - last.balls);
+                self.frames.push_back(last);
+            }
+            None => self.balls.clear(),
+        }
+    }
+
+    /// Oldest tick in the history.
+    pub fn first_tick(&self) -> u32 {
+        self.frames.front().map_or(0, |frame| frame.tick)
+    }
+
+    /// Newest tick in the history.
+    pub fn last_tick(&self) -> u32 {
+        self.frames.back().map_or(0, |frame| frame.tick)
+    }
+
+    /// Where the balls were at `tick`, empty outside of the history.
+    pub fn balls_at(&self, tick: u32) -> impl Iterator<Item = &BallSample> {
+        let mut start = 0;
+        let mut count = 0;
+        for frame in &self.frames {
+            if frame.tick == tick {
+                count = frame.balls;
+                break;
+            }
+            start += frame.balls;
+        }
+        self.balls.range(start..start + count)
+    }
+
+    /// Cell changes after the oldest tick, oldest first.
+    pub fn changes(&self) -> impl DoubleEndedIterator<Item = &CellChange> {
+        self.changes.iter()
+    }
+
+    /// Most cells a team won or lost over the history.
+    pub fn swing(&self) -> usize {
+        let (Some(first), Some(last)) = (self.frames.front(), self.frames.back()) else {
+            return 0;
+        };
+        first
+            .counts
+            .iter()
+            .zip(last.counts)
+            .map(|(before, after)| before.abs_diff(after))
+            .max()
+            .unwrap_or(0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::region_sim::{MatchSettings, TICK_SECONDS};
+
+    #[test]
+    fn clear_keeps_the_current_tick() {
+        let mut world = RegionWorld::new(&MatchSettings {
+            balls_per_team: 2,
+            seed: Some(3),
+            ..MatchSettings::default()
+        });
+        let mut history = History::new(&world);
+        for _ in 0..100 {
+            world.step(TICK_SECONDS);
+            history.record(&world);
+            world.clear_changes();
+        }
+        history.clear();
+
+        assert_eq!(history.first_tick(), world.tick());
+        assert_eq!(history.last_tick(), world.tick());
+        assert_eq!(history.changes().count(), 0);
+        let positions: Vec<Vec2> = history
+            .balls_at(world.tick())
+            .map(|sample| sample.position)
+            .collect();
+        let expected: Vec<Vec2> = world.balls().iter().map(|ball| ball.position).collect();
+        assert_eq!(positions, expected);
+
+        world.step(TICK_SECONDS);
+        history.record(&world);
+        assert_eq!(history.balls_at(world.tick()).count(), world.balls().len());
+    }
+}
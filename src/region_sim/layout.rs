@@ -0,0 +1,243 @@
+// Starting layouts for the Region Battle board. A layout only decides which team
+// owns each cell; the random ones draw from the match RNG so a seed always
+// produces the same board.
+use rand::{rngs::StdRng, seq::SliceRandom, Rng};
+
+use super::{Grid, TeamId};
+
+// Smoothing passes applied to the random noise of `Layout::RandomBlobs`
+const BLOB_SMOOTHING: usize = 4;
+// Width of each band of `Layout::Rings`, in bricks
+const RING_WIDTH: usize = 2;
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Layout {
+    /// Red on the left, blue on the right. The classic split.
+    #[default]
+    VerticalHalves,
+    /// Red at the bottom, blue at the top.
+    HorizontalHalves,
+    /// Split along the diagonal from the top-left to the bottom-right corner.
+    DiagonalHalves,
+    Checkerboard,
+    /// Concentric square bands around the center.
+    Rings,
+    /// Random noise smoothed by a cellular automaton into organic blobs.
+    RandomBlobs,
+    /// Random blobs, rebalanced so both teams own the same number of cells (one
+    /// cell apart on boards with an odd cell count).
+    FairRandom,
+}
+
+impl Layout {
+    pub const ALL: [Layout; 7] = [
+        Layout::VerticalHalves,
+        Layout::HorizontalHalves,
+        Layout::DiagonalHalves,
+        Layout::Checkerboard,
+        Layout::Rings,
+        Layout::RandomBlobs,
+        Layout::FairRandom,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Layout::VerticalHalves => "vertical",
+            Layout::HorizontalHalves => "horizontal",
+            Layout::DiagonalHalves => "diagonal",
+            Layout::Checkerboard => "checkerboard",
+            Layout::Rings => "rings",
+            Layout::RandomBlobs => "blobs",
+            Layout::FairRandom => "fair-random",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Layout> {
+        Layout::ALL.into_iter().find(|layout| layout.name() == name)
+    }
+
+    /// Whether the layout differs from seed to seed.
+    pub fn is_random(&self) -> bool {
+        matches!(self, Layout::RandomBlobs | Layout::FairRandom)
+    }
+
+    /// Owner of every cell of `grid`, indexed like `RegionWorld::cells`.
+    pub fn generate(&self, grid: Grid, rng: &mut StdRng) -> Vec<TeamId> {
+        let team = |blue: bool| if blue { TeamId::BLUE } else { TeamId::RED };
+        let (mid_column, mid_row) = (grid.columns / 2, grid.rows / 2);
+        let by_coords = |owner: &dyn Fn(usize, usize) -> bool| {
+            (0..grid.len())
+                .map(|cell| {
+                    let (column, row) = grid.coords(cell);
+                    team(owner(column, row))
+                })
+                .collect()
+        };
+        match self {
+            // the middle column or row of odd boards goes to red
+            Layout::VerticalHalves => by_coords(&|column, _| 2 * column >= grid.columns),
+            Layout::HorizontalHalves => by_coords(&|_, row| 2 * row >= grid.rows),
+            Layout::DiagonalHalves => by_coords(&|column, row| {
+                // scaled so non-square boards still split corner to corner, cells
+                // right on the diagonal alternate between the teams
+                let (across, up) = (column * (grid.rows - 1), row * (grid.columns - 1));
+                across > up || (across == up && column % 2 == 1)
+            }),
+            Layout::Checkerboard => by_coords(&|column, row| (column + row) % 2 == 1),
+            Layout::Rings => by_coords(&|column, row| {
+                let distance = column.abs_diff(mid_column).max(row.abs_diff(mid_row));
+                (distance / RING_WIDTH) % 2 == 1
+            }),
+            Layout::RandomBlobs => blobs(grid, rng),
+            Layout::FairRandom => {
+                let mut cells = blobs(grid, rng);
+                rebalance(grid, &mut cells, rng);
+                cells
+            }
+        }
+    }
+}
+
+fn blobs(grid: Grid, rng: &mut StdRng) -> Vec<TeamId> {
+    let mut cells: Vec<TeamId> = (0..grid.len())
+        .map(|_| {
+            if rng.gen_bool(0.5) {
+                TeamId::BLUE
+            } else {
+                TeamId::RED
+            }
+        })
+        .collect();
+    let mut next = cells.clone();
+    for _ in 0..BLOB_SMOOTHING {
+        for (cell, owner) in next.iter_mut().enumerate() {
+            // majority vote over the 3x3 neighbourhood, ties keep the owner
+            let blue = neighbours(grid, cell, true)
+                .filter(|other| cells[*other] == TeamId::BLUE)
+                .count();
+            let total = neighbours(grid, cell, true).count();
+            *owner = match (blue * 2).cmp(&total) {
+                std::cmp::Ordering::Greater => TeamId::BLUE,
+                std::cmp::Ordering::Less => TeamId::RED,
+                std::cmp::Ordering::Equal => cells[cell],
+            };
+        }
+        std::mem::swap(&mut cells, &mut next);
+    }
+    cells
+}
+
+// Hands cells on the border between the teams from the larger team to the smaller
+// one until their counts differ by at most one
+fn rebalance(grid: Grid, cells: &mut [TeamId], rng: &mut StdRng) {
+    loop {
+        let blue = cells.iter().filter(|owner| **owner == TeamId::BLUE).count();
+        let red = cells.len() - blue;
+        if red.abs_diff(blue) <= 1 {
+            return;
+        }
+        let (larger, smaller) = if red > blue {
+            (TeamId::RED, TeamId::BLUE)
+        } else {
+            (TeamId::BLUE, TeamId::RED)
+        };
+        let mut border: Vec<usize> = (0..cells.len())
+            .filter(|cell| {
+                cells[*cell] == larger
+                    && neighbours(grid, *cell, false).any(|other| cells[other] == smaller)
+            })
+            .collect();
+        if border.is_empty() {
+            // one team owns everything, start the other one somewhere
+            border.push(rng.gen_range(0..cells.len()));
+        }
+        border.shuffle(rng);
+        let needed = red.abs_diff(blue) / 2;
+        for cell in border.into_iter().take(needed) {
+            cells[cell] = smaller;
+        }
+    }
+}
+
+// The 3x3 neighbourhood of `cell` clipped to the board, optionally with the cell
+fn neighbours(grid: Grid, cell: usize, include_self: bool) -> impl Iterator<Item = usize> {
+    let (column, row) = grid.coords(cell);
+    let columns = column.saturating_sub(1)..=(column + 1).min(grid.columns - 1);
+    let rows = row.saturating_sub(1)..=(row + 1).min(grid.rows - 1);
+    rows.flat_map(move |other_row| {
+        columns
+            .clone()
+            .map(move |other_column| other_row * grid.columns + other_column)
+    })
+    .filter(move |other| include_self || *other != cell)
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::SeedableRng;
+
+    use super::*;
+
+    fn counts(cells: &[TeamId]) -> (usize, usize) {
+        let red = cells.iter().filter(|owner| **owner == TeamId::RED).count();
+        (red, cells.len() - red)
+    }
+
+    fn grid(columns: usize, rows: usize) -> Grid {
+        Grid {
+            columns,
+            rows,
+            brick_size: 20.,
+        }
+    }
+
+    #[test]
+    fn fair_random_counts_differ_by_at_most_one() {
+        for (columns, rows) in [(31, 31), (30, 30), (8, 13), (4, 4)] {
+            for seed in 0..20 {
+                let mut rng = StdRng::seed_from_u64(seed);
+                let cells = Layout::FairRandom.generate(grid(columns, rows), &mut rng);
+                let (red, blue) = counts(&cells);
+                assert!(
+                    red.abs_diff(blue) <= 1,
+                    "{columns}x{rows} seed {seed}: {red} to {blue}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn halves_mirror_each_other_on_even_boards() {
+        let grid = grid(30, 30);
+        let mut rng = StdRng::seed_from_u64(0);
+        let vertical = Layout::VerticalHalves.generate(grid, &mut rng);
+        let horizontal = Layout::HorizontalHalves.generate(grid, &mut rng);
+        for cell in 0..grid.len() {
+            let (column, row) = grid.coords(cell);
+            let across = row * grid.columns + grid.columns - 1 - column;
+            let up = (grid.rows - 1 - row) * grid.columns + column;
+            assert_ne!(vertical[cell], vertical[across], "{column}, {row}");
+            assert_ne!(horizontal[cell], horizontal[up], "{column}, {row}");
+        }
+    }
+
+    #[test]
+    fn halves_split_evenly() {
+        let mut rng = StdRng::seed_from_u64(0);
+        for layout in [
+            Layout::VerticalHalves,
+            Layout::HorizontalHalves,
+            Layout::DiagonalHalves,
+        ] {
+            for (columns, rows) in [(30, 30), (12, 8)] {
+                let (red, blue) = counts(&layout.generate(grid(columns, rows), &mut rng));
+                assert_eq!(red, blue, "{layout:?} {columns}x{rows}");
+            }
+        }
+        // the middle line of an odd board goes to red
+        for layout in [Layout::VerticalHalves, Layout::HorizontalHalves] {
+            let (red, blue) = counts(&layout.generate(grid(31, 31), &mut rng));
+            assert_eq!(red - blue, 31, "{layout:?}");
+        }
+    }
+}
@@ -0,0 +1,299 @@
+// Text format for hand-made Region Battle arenas. A map is a small header of
+// `key: value` lines, a `---` separator and the board, one character per cell:
+//
+//     name: Crossroads
+//     author: someone
+//     ---
+//     ###########
+//     #1RR...BB2#
+//     ###########
+//
+// `R`/`B` are red/blue bricks, `.` neutral bricks, `r`/`b`/`*` armored red, blue
+// and neutral bricks, `#` walls, ` ` void, and `1`/`2` a red/blue brick with a
+// ball of that team starting on it. The first line of the board is its top row.
+// Shorter lines are padded with void.
+use std::fmt;
+
+use super::{Cell, TeamId, ARMOR_HITS};
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct RegionMap {
+    pub name: String,
+    pub author: Option<String>,
+    pub description: Option<String>,
+    pub columns: usize,
+    pub rows: usize,
+    /// Indexed like `RegionWorld::cells`, so row `0` is the bottom of the board.
+    pub cells: Vec<Cell>,
+    /// Cell index of every starting ball.
+    pub spawns: Vec<(TeamId, usize)>,
+}
+
+/// Why a map failed to parse. `line` and `column` are 1-based and point at the
+/// offending character.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MapError {
+    pub line: usize,
+    pub column: usize,
+    pub kind: MapErrorKind,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MapErrorKind {
+    MissingSeparator,
+    MalformedHeader,
+    UnknownKey(String),
+    DuplicateKey(String),
+    UnknownTile(char),
+    EmptyBoard,
+    MissingSpawn(TeamId),
+}
+
+impl fmt::Display for MapError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}, column {}: ", self.line, self.column)?;
+        match &self.kind {
+            MapErrorKind::MissingSeparator => {
+                write!(f, "expected a `---` line between the header and the board")
+            }
+            MapErrorKind::MalformedHeader => write!(f, "expected a `key: value` header line"),
+            MapErrorKind::UnknownKey(key) => write!(
+                f,
+                "unknown header key `{key}`, expected `name`, `author` or `description`"
+            ),
+            MapErrorKind::DuplicateKey(key) => write!(f, "header key `{key}` is set twice"),
+            MapErrorKind::UnknownTile(tile) => write!(
+                f,
+                "unknown tile {tile:?}, expected one of `R`, `B`, `.`, `r`, `b`, `*`, `#`, ` `, `1` or `2`"
+            ),
+            MapErrorKind::EmptyBoard => write!(f, "the board has no rows"),
+            MapErrorKind::MissingSpawn(team) => {
+                write!(f, "the board needs at least one `{}` spawn", team.0 + 1)
+            }
+        }
+    }
+}
+
+impl std::error::Error for MapError {}
+
+fn armored(owner: Option<TeamId>) -> Cell {
+    Cell::Armored {
+        owner,
+        hits: ARMOR_HITS,
+    }
+}
+
+impl RegionMap {
+    /// Parses a map. `fallback_name` is used when the header has no `name`,
+    /// usually the file stem.
+    pub fn parse(source: &str, fallback_name: &str) -> Result<RegionMap, MapError> {
+        let error = |line: usize, column: usize, kind| MapError { line, column, kind };
+        let mut lines = source
+            .lines()
+            .enumerate()
+            .map(|(index, line)| (index + 1, line));
+
+        let mut name = None;
+        let mut author = None;
+        let mut description = None;
+        let mut separator = None;
+        for (number, line) in lines.by_ref() {
+            let line = line.trim_end_matches('\r');
+            if line.trim() == "---" {
+                separator = Some(number);
+                break;
+            }
+            if line.trim().is_empty() {
+                continue;
+            }
+            let (key, value) = line
+                .split_once(':')
+                .ok_or_else(|| error(number, 1, MapErrorKind::MalformedHeader))?;
+            let key = key.trim();
+            let slot = match key {
+                "name" => &mut name,
+                "author" => &mut author,
+                "description" => &mut description,
+                _ => return Err(error(number, 1, MapErrorKind::UnknownKey(key.to_string()))),
+            };
+            if slot.is_some() {
+                return Err(error(
+                    number,
+                    1,
+                    MapErrorKind::DuplicateKey(key.to_string()),
+                ));
+            }
+            *slot = Some(value.trim().to_string());
+        }
+        let Some(separator) = separator else {
+            let end = source.lines().count() + 1;
+            return Err(error(end, 1, MapErrorKind::MissingSeparator));
+        };
+
+        let board: Vec<(usize, &str)> = lines
+            .map(|(number, line)| (number, line.trim_end_matches('\r')))
+            .collect();
+        // trailing blank lines are an editor artifact, not void rows
+        let rows_used = board
+            .iter()
+            .rposition(|(_, line)| !line.is_empty())
+            .map_or(0, |last| last + 1);
+        let board = &board[..rows_used];
+        let end_line = board.last().map_or(separator, |(number, _)| *number) + 1;
+        let rows = board.len();
+        let columns = board
+            .iter()
+            .map(|(_, line)| line.chars().count())
+            .max()
+            .unwrap_or(0);
+        if rows == 0 || columns == 0 {
+            return Err(error(end_line, 1, MapErrorKind::EmptyBoard));
+        }
+
+        let mut cells = vec![Cell::Void; columns * rows];
+        let mut spawns = Vec::new();
+        for (index, (number, line)) in board.iter().enumerate() {
+            let row = rows - 1 - index;
+            for (column, tile) in line.chars().enumerate() {
+                let cell = row * columns + column;
+                cells[cell] = match tile {
+                    'R' => Cell::Team(TeamId::RED),
+                    'B' => Cell::Team(TeamId::BLUE),
+                    '.' => Cell::Neutral,
+                    'r' => armored(Some(TeamId::RED)),
+                    'b' => armored(Some(TeamId::BLUE)),
+                    '*' => armored(None),
+                    '#' => Cell::Wall,
+                    ' ' => Cell::Void,
+                    '1' | '2' => {
+                        let team = if tile == '1' {
+                            TeamId::RED
+                        } else {
+                            TeamId::BLUE
+                        };
+                        spawns.push((team, cell));
+                        Cell::Team(team)
+                    }
+                    _ => return Err(error(*number, column + 1, MapErrorKind::UnknownTile(tile))),
+                };
+            }
+        }
+        for team in TeamId::ALL {
+            if !spawns.iter().any(|(spawn, _)| *spawn == team) {
+                return Err(error(end_line, 1, MapErrorKind::MissingSpawn(team)));
+            }
+        }
+
+        Ok(RegionMap {
+            name: name.unwrap_or_else(|| fallback_name.to_string()),
+            author,
+            description,
+            columns,
+            rows,
+            cells,
+            spawns,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse_error(source: &str) -> MapError {
+        RegionMap::parse(source, "test").unwrap_err()
+    }
+
+    // The board of `map` as it would be written in a map file
+    fn board_text(map: &RegionMap) -> String {
+        let mut text = String::new();
+        for row in (0..map.rows).rev() {
+            let line: String = (0..map.columns)
+                .map(|column| {
+                    let cell = row * map.columns + column;
+                    match map.spawns.iter().find(|(_, spawn)| *spawn == cell) {
+                        Some((team, _)) => char::from(b'1' + team.0),
+                        None => match map.cells[cell] {
+                            Cell::Team(TeamId::RED) => 'R',
+                            Cell::Team(_) => 'B',
+                            Cell::Neutral => '.',
+                            Cell::Armored {
+                                owner: Some(TeamId::RED),
+                                ..
+                            } => 'r',
+                            Cell::Armored { owner: Some(_), .. } => 'b',
+                            Cell::Armored { owner: None, .. } => '*',
+                            Cell::Wall => '#',
+                            Cell::Void => ' ',
+                        },
+                    }
+                })
+                .collect();
+            text.push_str(line.trim_end());
+            text.push('\n');
+        }
+        text
+    }
+
+    #[test]
+    fn unknown_tile_reports_its_line_and_column() {
+        let error = parse_error("name: Test\n---\n1R.\nRB?2\n");
+        assert_eq!(
+            error,
+            MapError {
+                line: 4,
+                column: 3,
+                kind: MapErrorKind::UnknownTile('?'),
+            }
+        );
+        assert!(error.to_string().starts_with("line 4, column 3: "));
+    }
+
+    #[test]
+    fn header_needs_a_separator() {
+        let error = parse_error("name: Test\nauthor: someone\n");
+        assert_eq!(error.kind, MapErrorKind::MissingSeparator);
+        assert_eq!(error.line, 3);
+    }
+
+    #[test]
+    fn every_team_needs_a_spawn() {
+        let error = parse_error("---\nR1B\nRBB\n");
+        assert_eq!(error.kind, MapErrorKind::MissingSpawn(TeamId::BLUE));
+    }
+
+    #[test]
+    fn ragged_rows_are_padded_with_void() {
+        let map = RegionMap::parse("---\n1RB\nR\n.B2#\n", "ragged").unwrap();
+        assert_eq!(map.name, "ragged");
+        assert_eq!((map.columns, map.rows), (4, 3));
+        // the middle row only has its first cell
+        assert_eq!(map.cells[4], Cell::Team(TeamId::RED));
+        assert_eq!(&map.cells[5..8], [Cell::Void; 3]);
+        // the last line is the bottom row, and the first is padded too
+        assert_eq!(map.cells[3], Cell::Wall);
+        assert_eq!(map.cells[11], Cell::Void);
+        assert_eq!(map.spawns, [(TeamId::RED, 8), (TeamId::BLUE, 2)]);
+    }
+
+    #[test]
+    fn shipped_maps_round_trip() {
+        for source in [
+            include_str!("../../assets/maps/crossroads.map"),
+            include_str!("../../assets/maps/fortress.map"),
+            include_str!("../../assets/maps/islands.map"),
+        ] {
+            let map = RegionMap::parse(source, "shipped").unwrap();
+            let (header, board) = source.split_once("---\n").unwrap();
+            let board: String = board
+                .lines()
+                .map(|line| line.trim_end().to_string() + "\n")
+                .collect();
+            assert_eq!(
+                board_text(&map),
+                board.trim_end_matches('\n').to_string() + "\n"
+            );
+            assert!(header.contains(&format!("name: {}", map.name)));
+        }
+    }
+}
@@ -0,0 +1,92 @@
+// Timed pickups. While any kind is enabled a pickup appears on a random cell
+// every few seconds, and the first ball to roll over it gets the effect.
+use bevy::math::Vec2;
+
+use super::TeamId;
+
+// a new pickup every five seconds at the fixed tick rate
+pub const PICKUP_INTERVAL: u32 = 64 * 5;
+pub const MAX_PICKUPS: usize = 3;
+pub const PICKUP_RADIUS: f32 = 8.;
+pub const SPEED_BOOST: f32 = 1.6;
+pub const GROWTH: f32 = 1.8;
+// half the angle between the two balls of a split
+pub const SPLIT_ANGLE: f32 = 0.5;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum PowerUpKind {
+    /// The ball moves faster for a while.
+    SpeedBoost,
+    /// The ball gets bigger for a while.
+    Growth,
+    /// The ball splits in two for the rest of the match.
+    MultiBall,
+    /// Enemy balls can not capture the team's cells for a while.
+    Shield,
+    /// Captures the 3x3 cells around the pickup at once.
+    PaintBomb,
+}
+
+impl PowerUpKind {
+    pub const ALL: [PowerUpKind; 5] = [
+        PowerUpKind::SpeedBoost,
+        PowerUpKind::Growth,
+        PowerUpKind::MultiBall,
+        PowerUpKind::Shield,
+        PowerUpKind::PaintBomb,
+    ];
+
+    /// Name used on the command line.
+    pub fn name(self) -> &'static str {
+        match self {
+            PowerUpKind::SpeedBoost => "speed",
+            PowerUpKind::Growth => "growth",
+            PowerUpKind::MultiBall => "multi-ball",
+            PowerUpKind::Shield => "shield",
+            PowerUpKind::PaintBomb => "paint-bomb",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<PowerUpKind> {
+        PowerUpKind::ALL
+            .into_iter()
+            .find(|kind| kind.name() == name)
+    }
+
+    /// How many ticks the effect lasts, `None` for instant ones.
+    pub fn duration(self) -> Option<u32> {
+        match self {
+            PowerUpKind::SpeedBoost | PowerUpKind::Growth => Some(64 * 8),
+            PowerUpKind::Shield => Some(64 * 5),
+            PowerUpKind::MultiBall | PowerUpKind::PaintBomb => None,
+        }
+    }
+}
+
+/// A power-up waiting on the board.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Pickup {
+    /// Unique for the match, so renderers can tell pickups apart.
+    pub id: u32,
+    pub kind: PowerUpKind,
+    pub cell: usize,
+    pub position: Vec2,
+}
+
+/// A timed power-up in effect. `ball` is the ball it applies to, or `None` for
+/// effects on the whole team.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Effect {
+    pub kind: PowerUpKind,
+    pub team: TeamId,
+    pub ball: Option<usize>,
+    pub ticks_left: u32,
+}
+
+/// A pickup some ball just collected.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PickedUp {
+    pub kind: PowerUpKind,
+    pub team: TeamId,
+    pub position: Vec2,
+}
@@ -0,0 +1,877 @@
+// Match recordings. The simulation is deterministic, so a replay only stores the
+// match settings and the player inputs with the tick they happened on, and
+// plays the match again by stepping a fresh world with the same inputs. Board
+// snapshots every few seconds catch a replay that no longer plays back the
+// same, instead of silently showing a different match.
+//
+// The file is binary, numbers are little-endian floats or LEB128 varints:
+//
+//     "RBRP", format, simulation version, settings, tick length, ticks,
+//     inputs, snapshots
+//
+// A replay only plays back on the `SIM_VERSION` that recorded it, physics
+// changes between versions would make it drift.
+use std::fmt;
+
+use bevy::math::Vec2;
+
+use super::{
+    Cell, Layout, MatchSettings, PowerUpKind, RegionMap, RegionWorld, SpeedCurve,
+    StalemateResponse, SteeringInput, TeamId, SIM_VERSION,
+};
+
+const MAGIC: &[u8; 4] = b"RBRP";
+const FORMAT: u8 = 2;
+// four seconds between board snapshots, and between the worlds `Playback`
+// keeps to jump back to
+const SNAPSHOT_TICKS: u32 = 256;
+// limits for what a damaged file may ask us to allocate or simulate
+const MAX_BOARD_SIZE: usize = 1024;
+const MAX_CELLS: u64 = 1 << 22;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ReplayError {
+    NotAReplay,
+    /// Written in a file format this version can not read.
+    Format {
+        found: u8,
+    },
+    /// Recorded with another `SIM_VERSION`.
+    Version {
+        found: u32,
+    },
+    /// The file ends early or holds values no replay can contain.
+    Corrupt(&'static str),
+    /// Playing the inputs back gave a different board than the recording.
+    Desync {
+        tick: u32,
+    },
+}
+
+impl fmt::Display for ReplayError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ReplayError::NotAReplay => write!(f, "not a Region Battle replay"),
+            ReplayError::Format { found } => {
+                write!(f, "replay format {found} is not supported, expected {FORMAT}")
+            }
+            ReplayError::Version { found } => write!(
+                f,
+                "replay was recorded with simulation version {found}, this game plays version {SIM_VERSION}"
+            ),
+            ReplayError::Corrupt(what) => write!(f, "replay is damaged: {what}"),
+            ReplayError::Desync { tick } => {
+                write!(f, "replay no longer matches the recording at tick {tick}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for ReplayError {}
+
+/// Something a player did during a match.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ReplayInput {
+    Steer {
+        team: TeamId,
+        input: SteeringInput,
+    },
+    Barrier {
+        team: TeamId,
+        center: Vec2,
+        vertical: bool,
+    },
+}
+
+impl ReplayInput {
+    /// Applies the input to `world`, `false` if it had no effect.
+    pub fn apply(self, world: &mut RegionWorld) -> bool {
+        match self {
+            ReplayInput::Steer { team, input } => {
+                world.steer(team, input);
+                true
+            }
+            ReplayInput::Barrier {
+                team,
+                center,
+                vertical,
+            } => world.place_barrier(team, center, vertical),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Snapshot {
+    tick: u32,
+    cells: Vec<Cell>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Replay {
+    settings: MatchSettings,
+    tick_seconds: f32,
+    ticks: u32,
+    // sorted by tick, each applied right before that tick's step
+    inputs: Vec<(u32, ReplayInput)>,
+    snapshots: Vec<Snapshot>,
+}
+
+impl Replay {
+    /// Length of the match in ticks.
+    pub fn ticks(&self) -> u32 {
+        self.ticks
+    }
+
+    pub fn tick_seconds(&self) -> f32 {
+        self.tick_seconds
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut writer = Writer::default();
+        writer.bytes.extend_from_slice(MAGIC);
+        writer.u8(FORMAT);
+        writer.varint(SIM_VERSION as u64);
+        self.write(&mut writer);
+        writer.bytes
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Replay, ReplayError> {
+        if !bytes.starts_with(MAGIC) {
+            return Err(ReplayError::NotAReplay);
+        }
+        let mut reader = Reader::new(bytes, MAGIC.len());
+        let format = reader.u8()?;
+        if format != FORMAT {
+            return Err(ReplayError::Format { found: format });
+        }
+        let version = reader.u32()?;
+        if version != SIM_VERSION {
+            return Err(ReplayError::Version { found: version });
+        }
+        let replay = Replay::read(&mut reader)?;
+        reader.finish()?;
+        Ok(replay)
+    }
+
+    // Everything after the header, shared with saved matches
+    pub(super) fn write(&self, writer: &mut Writer) {
+        writer.settings(&self.settings);
+        writer.f32(self.tick_seconds);
+        writer.varint(self.ticks as u64);
+
+        writer.varint(self.inputs.len() as u64);
+        let mut last_tick = 0;
+        for (tick, input) in &self.inputs {
+            writer.varint((tick - last_tick) as u64);
+            last_tick = *tick;
+            match *input {
+                ReplayInput::Steer { team, input } => {
+                    writer.u8(0);
+                    writer.u8(team.0);
+                    writer.vec2(input.direction);
+                    writer.bool(input.boost);
+                }
+                ReplayInput::Barrier {
+                    team,
+                    center,
+                    vertical,
+                } => {
+                    writer.u8(1);
+                    writer.u8(team.0);
+                    writer.vec2(center);
+                    writer.bool(vertical);
+                }
+            }
+        }
+
+        writer.varint(self.snapshots.len() as u64);
+        for snapshot in &self.snapshots {
+            writer.varint(snapshot.tick as u64);
+            writer.cells(&snapshot.cells);
+        }
+    }
+
+    pub(super) fn read(reader: &mut Reader) -> Result<Replay, ReplayError> {
+        let settings = reader.settings()?;
+        let tick_seconds = reader.f32()?;
+        let ticks = reader.u32()?;
+
+        let mut inputs = Vec::new();
+        let mut tick = 0u32;
+        for _ in 0..reader.len()? {
+            tick = tick
+                .checked_add(reader.u32()?)
+                .ok_or(ReplayError::Corrupt("input tick out of range"))?;
+            let kind = reader.u8()?;
+            let team = reader.team()?;
+            let position = reader.vec2()?;
+            let flag = reader.bool()?;
+            let input = match kind {
+                0 => ReplayInput::Steer {
+                    team,
+                    input: SteeringInput {
+                        direction: position,
+                        boost: flag,
+                    },
+                },
+                1 => ReplayInput::Barrier {
+                    team,
+                    center: position,
+                    vertical: flag,
+                },
+                _ => return Err(ReplayError::Corrupt("unknown input")),
+            };
+            inputs.push((tick, input));
+        }
+
+        let mut snapshots = Vec::new();
+        for _ in 0..reader.len()? {
+            let tick = reader.u32()?;
+            let cells = reader.cells()?;
+            snapshots.push(Snapshot { tick, cells });
+        }
+        Ok(Replay {
+            settings,
+            tick_seconds,
+            ticks,
+            inputs,
+            snapshots,
+        })
+    }
+}
+
+/// Writes a replay while the match is played.
+#[derive(Debug, Clone)]
+pub struct Recorder {
+    replay: Replay,
+    // last steering input of each team, so only changes are stored
+    steering: Vec<SteeringInput>,
+}
+
+impl Recorder {
+    pub fn new(settings: &MatchSettings, tick_seconds: f32) -> Self {
+        Self {
+            replay: Replay {
+                settings: settings.clone(),
+                tick_seconds,
+                ticks: 0,
+                inputs: Vec::new(),
+                snapshots: Vec::new(),
+            },
+            steering: vec![SteeringInput::default(); TeamId::ALL.len()],
+        }
+    }
+
+    /// Carries on recording `replay` from its last tick.
+    pub fn resume(replay: Replay) -> Self {
+        let mut steering = vec![SteeringInput::default(); TeamId::ALL.len()];
+        for (_, input) in &replay.inputs {
+            if let ReplayInput::Steer { team, input } = *input {
+                steering[team.0 as usize] = input;
+            }
+        }
+        Self { replay, steering }
+    }
+
+    /// Applies `input` to `world` and records it for the coming tick.
+    pub fn apply(&mut self, world: &mut RegionWorld, input: ReplayInput) {
+        if let ReplayInput::Steer { team, input } = input {
+            let Some(last) = self.steering.get_mut(team.0 as usize) else {
+                return;
+            };
+            if *last == input {
+                return;
+            }
+            *last = input;
+        }
+        if input.apply(world) {
+            self.replay.inputs.push((world.tick(), input));
+        }
+    }
+
+    /// Call after every step of `world`.
+    pub fn record_tick(&mut self, world: &RegionWorld) {
+        self.replay.ticks = world.tick();
+        if world.tick().is_multiple_of(SNAPSHOT_TICKS) {
+            self.replay.snapshots.push(Snapshot {
+                tick: world.tick(),
+                cells: world.cells().to_vec(),
+            });
+        }
+    }
+
+    pub fn replay(&self) -> &Replay {
+        &self.replay
+    }
+}
+
+/// Plays a replay back, forwards one tick at a time or jumping to any tick.
+#[derive(Debug, Clone)]
+pub struct Playback {
+    replay: Replay,
+    // the world every `SNAPSHOT_TICKS` ticks, as far as the replay was played
+    checkpoints: Vec<RegionWorld>,
+}
+
+impl Playback {
+    pub fn new(replay: Replay) -> Self {
+        let start = RegionWorld::new(&replay.settings);
+        Self {
+            replay,
+            checkpoints: vec![start],
+        }
+    }
+
+    pub fn replay(&self) -> &Replay {
+        &self.replay
+    }
+
+    /// The world before the first tick.
+    pub fn start(&self) -> RegionWorld {
+        self.checkpoints[0].clone()
+    }
+
+    /// Plays the next tick on `world`, which has to come from this playback.
+    /// Does nothing at the end of the replay.
+    pub fn step(&mut self, world: &mut RegionWorld) -> Result<(), ReplayError> {
+        let tick = world.tick();
+        if tick >= self.replay.ticks {
+            return Ok(());
+        }
+        let first = self.replay.inputs.partition_point(|(at, _)| *at < tick);
+        for (_, input) in self.replay.inputs[first..]
+            .iter()
+            .take_while(|(at, _)| *at == tick)
+        {
+            input.apply(world);
+        }
+        world.step(self.replay.tick_seconds);
+
+        let tick = world.tick();
+        if let Ok(index) = self
+            .replay
+            .snapshots
+            .binary_search_by_key(&tick, |snapshot| snapshot.tick)
+        {
+            if self.replay.snapshots[index].cells != world.cells() {
+                return Err(ReplayError::Desync { tick });
+            }
+        }
+        if tick == self.checkpoints.len() as u32 * SNAPSHOT_TICKS {
+            let mut checkpoint = world.clone();
+            // seeking to it must not report this tick's changes again
+            checkpoint.clear_changes();
+            self.checkpoints.push(checkpoint);
+        }
+        Ok(())
+    }
+
+    /// Moves `world` to `tick`, replaying from the closest earlier checkpoint.
+    /// Every cell is reported as captured afterwards, so views redraw the board,
+    /// and the events of the skipped ticks are dropped.
+    pub fn seek(&mut self, world: &mut RegionWorld, tick: u32) -> Result<(), ReplayError> {
+        let tick = tick.min(self.replay.ticks);
+        let index = ((tick / SNAPSHOT_TICKS) as usize).min(self.checkpoints.len() - 1);
+        let checkpoint = &self.checkpoints[index];
+        // keep going from `world` when it is already between the checkpoint
+        // and the target
+        if world.tick() > tick || world.tick() < checkpoint.tick() {
+            *world = checkpoint.clone();
+        }
+        while world.tick() < tick {
+            self.step(world)?;
+            world.clear_changes();
+        }
+        world.touch_all_cells();
+        Ok(())
+    }
+}
+
+#[derive(Default)]
+pub(super) struct Writer {
+    pub(super) bytes: Vec<u8>,
+}
+
+impl Writer {
+    pub(super) fn u8(&mut self, value: u8) {
+        self.bytes.push(value);
+    }
+
+    pub(super) fn bool(&mut self, value: bool) {
+        self.u8(value as u8);
+    }
+
+    pub(super) fn varint(&mut self, mut value: u64) {
+        while value >= 0x80 {
+            self.u8(value as u8 | 0x80);
+            value >>= 7;
+        }
+        self.u8(value as u8);
+    }
+
+    pub(super) fn f32(&mut self, value: f32) {
+        self.bytes.extend_from_slice(&value.to_le_bytes());
+    }
+
+    pub(super) fn vec2(&mut self, value: Vec2) {
+        self.f32(value.x);
+        self.f32(value.y);
+    }
+
+    pub(super) fn str(&mut self, value: &str) {
+        self.varint(value.len() as u64);
+        self.bytes.extend_from_slice(value.as_bytes());
+    }
+
+    fn option<T>(&mut self, value: Option<T>, write: impl FnOnce(&mut Self, T)) {
+        self.bool(value.is_some());
+        if let Some(value) = value {
+            write(self, value);
+        }
+    }
+
+    // Run-length encoded, boards are mostly long runs of one team
+    pub(super) fn cells(&mut self, cells: &[Cell]) {
+        self.varint(cells.len() as u64);
+        let mut runs = Vec::new();
+        for cell in cells {
+            match runs.last_mut() {
+                Some((last, count)) if last == cell => *count += 1,
+                _ => runs.push((*cell, 1)),
+            }
+        }
+        self.varint(runs.len() as u64);
+        for (cell, count) in runs {
+            self.u8(cell_code(cell));
+            self.varint(count);
+        }
+    }
+
+    fn settings(&mut self, settings: &MatchSettings) {
+        self.varint(settings.board_size as u64);
+        self.f32(settings.ball_speeds[0]);
+        self.f32(settings.ball_speeds[1]);
+        self.varint(settings.balls_per_team as u64);
+        self.str(settings.layout.name());
+        self.option(settings.seed, Self::varint);
+        self.option(settings.map.as_ref(), Self::map);
+        self.varint(settings.power_ups.len() as u64);
+        for kind in &settings.power_ups {
+            self.str(kind.name());
+        }
+        self.bool(settings.steering);
+        self.bool(settings.boost);
+        self.bool(settings.barriers);
+        self.bool(settings.ball_collisions);
+        self.f32(settings.restitution);
+        self.bool(settings.contact_steals);
+        self.option(settings.stalemate, |writer, response| {
+            writer.str(response.name())
+        });
+        self.f32(settings.speed_curve.per_capture);
+        self.f32(settings.speed_curve.per_second);
+        self.f32(settings.speed_curve.max_speed);
+        self.option(settings.speed_curve.sudden_death, |writer, ticks| {
+            writer.varint(ticks as u64)
+        });
+        self.option(settings.tick_limit, |writer, ticks| {
+            writer.varint(ticks as u64)
+        });
+    }
+
+    fn map(&mut self, map: &RegionMap) {
+        self.str(&map.name);
+        self.option(map.author.as_deref(), Self::str);
+        self.option(map.description.as_deref(), Self::str);
+        self.varint(map.columns as u64);
+        self.varint(map.rows as u64);
+        self.cells(&map.cells);
+        self.varint(map.spawns.len() as u64);
+        for (team, cell) in &map.spawns {
+            self.u8(team.0);
+            self.varint(*cell as u64);
+        }
+    }
+}
+
+pub(super) struct Reader<'a> {
+    bytes: &'a [u8],
+    position: usize,
+}
+
+impl<'a> Reader<'a> {
+    pub(super) fn new(bytes: &'a [u8], position: usize) -> Self {
+        Self { bytes, position }
+    }
+
+    /// Fails unless everything was read.
+    pub(super) fn finish(&self) -> Result<(), ReplayError> {
+        if self.position != self.bytes.len() {
+            return Err(ReplayError::Corrupt("trailing data"));
+        }
+        Ok(())
+    }
+
+    fn take(&mut self, count: usize) -> Result<&[u8], ReplayError> {
+        let end = self
+            .position
+            .checked_add(count)
+            .filter(|end| *end <= self.bytes.len())
+            .ok_or(ReplayError::Corrupt("unexpected end of file"))?;
+        let bytes = &self.bytes[self.position..end];
+        self.position = end;
+        Ok(bytes)
+    }
+
+    pub(super) fn u8(&mut self) -> Result<u8, ReplayError> {
+        Ok(self.take(1)?[0])
+    }
+
+    pub(super) fn bool(&mut self) -> Result<bool, ReplayError> {
+        match self.u8()? {
+            0 => Ok(false),
+            1 => Ok(true),
+            _ => Err(ReplayError::Corrupt("invalid flag")),
+        }
+    }
+
+    fn varint(&mut self) -> Result<u64, ReplayError> {
+        let mut value = 0u64;
+        for shift in (0..64).step_by(7) {
+            let byte = self.u8()?;
+            value |= ((byte & 0x7f) as u64) << shift;
+            if byte & 0x80 == 0 {
+                return Ok(value);
+            }
+        }
+        Err(ReplayError::Corrupt("number too long"))
+    }
+
+    pub(super) fn u32(&mut self) -> Result<u32, ReplayError> {
+        u32::try_from(self.varint()?).map_err(|_| ReplayError::Corrupt("number out of range"))
+    }
+
+    // A count of things that follow, each at least a byte long, so a damaged
+    // count can not make us allocate more than the file could hold
+    pub(super) fn len(&mut self) -> Result<usize, ReplayError> {
+        let len = self.varint()?;
+        if len > (self.bytes.len() - self.position) as u64 {
+            return Err(ReplayError::Corrupt("length out of range"));
+        }
+        Ok(len as usize)
+    }
+
+    pub(super) fn f32(&mut self) -> Result<f32, ReplayError> {
+        let bytes = self.take(4)?;
+        Ok(f32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    }
+
+    pub(super) fn vec2(&mut self) -> Result<Vec2, ReplayError> {
+        Ok(Vec2::new(self.f32()?, self.f32()?))
+    }
+
+    pub(super) fn str(&mut self) -> Result<String, ReplayError> {
+        let len = self.len()?;
+        String::from_utf8(self.take(len)?.to_vec())
+            .map_err(|_| ReplayError::Corrupt("invalid text"))
+    }
+
+    fn option<T>(
+        &mut self,
+        read: impl FnOnce(&mut Self) -> Result<T, ReplayError>,
+    ) -> Result<Option<T>, ReplayError> {
+        if self.bool()? {
+            Ok(Some(read(self)?))
+        } else {
+            Ok(None)
+        }
+    }
+
+    pub(super) fn team(&mut self) -> Result<TeamId, ReplayError> {
+        let team = TeamId(self.u8()?);
+        if !TeamId::ALL.contains(&team) {
+            return Err(ReplayError::Corrupt("unknown team"));
+        }
+        Ok(team)
+    }
+
+    pub(super) fn cells(&mut self) -> Result<Vec<Cell>, ReplayError> {
+        let count = self.varint()?;
+        if count > MAX_CELLS {
+            return Err(ReplayError::Corrupt("board too large"));
+        }
+        let mut cells = Vec::new();
+        for _ in 0..self.len()? {
+            let cell = cell_from_code(self.u8()?)?;
+            let run = self.varint()?;
+            if cells.len() as u64 + run > count {
+                return Err(ReplayError::Corrupt("board larger than its size"));
+            }
+            cells.resize(cells.len() + run as usize, cell);
+        }
+        if cells.len() as u64 != count {
+            return Err(ReplayError::Corrupt("board smaller than its size"));
+        }
+        Ok(cells)
+    }
+
+    fn settings(&mut self) -> Result<MatchSettings, ReplayError> {
+        let board_size = self.u32()? as usize;
+        if !(4..=MAX_BOARD_SIZE).contains(&board_size) {
+            return Err(ReplayError::Corrupt("board size out of range"));
+        }
+        let ball_speeds = [self.f32()?, self.f32()?];
+        let balls_per_team = self.u32()? as usize;
+        // a team's balls share its starting column, more would not fit on the board
+        if balls_per_team > board_size {
+            return Err(ReplayError::Corrupt("too many balls"));
+        }
+        let layout =
+            Layout::from_name(&self.str()?).ok_or(ReplayError::Corrupt("unknown layout"))?;
+        let seed = self.option(Self::varint)?;
+        let map = self.option(Self::map)?;
+        let mut power_ups = Vec::new();
+        for _ in 0..self.len()? {
+            power_ups.push(
+                PowerUpKind::from_name(&self.str()?)
+                    .ok_or(ReplayError::Corrupt("unknown power-up"))?,
+            );
+        }
+        Ok(MatchSettings {
+            board_size,
+            ball_speeds,
+            balls_per_team,
+            layout,
+            seed,
+            map,
+            power_ups,
+            steering: self.bool()?,
+            boost: self.bool()?,
+            barriers: self.bool()?,
+            ball_collisions: self.bool()?,
+            restitution: self.f32()?,
+            contact_steals: self.bool()?,
+            stalemate: self.option(|reader| {
+                StalemateResponse::from_name(&reader.str()?)
+                    .ok_or(ReplayError::Corrupt("unknown stalemate response"))
+            })?,
+            speed_curve: SpeedCurve {
+                per_capture: self.f32()?,
+                per_second: self.f32()?,
+                max_speed: self.f32()?,
+                sudden_death: self.option(Self::u32)?,
+            },
+            tick_limit: self.option(Self::u32)?,
+        })
+    }
+
+    fn map(&mut self) -> Result<RegionMap, ReplayError> {
+        let name = self.str()?;
+        let author = self.option(Self::str)?;
+        let description = self.option(Self::str)?;
+        let columns = self.u32()? as usize;
+        let rows = self.u32()? as usize;
+        if columns == 0 || rows == 0 {
+            return Err(ReplayError::Corrupt("empty map"));
+        }
+        let cells = self.cells()?;
+        if columns.checked_mul(rows) != Some(cells.len()) {
+            return Err(ReplayError::Corrupt("map size does not match its board"));
+        }
+        let mut spawns = Vec::new();
+        for _ in 0..self.len()? {
+            let team = self.team()?;
+            let cell = self.u32()? as usize;
+            if cell >= cells.len() {
+                return Err(ReplayError::Corrupt("spawn outside of the map"));
+            }
+            spawns.push((team, cell));
+        }
+        Ok(RegionMap {
+            name,
+            author,
+            description,
+            columns,
+            rows,
+            cells,
+            spawns,
+        })
+    }
+}
+
+// One byte per cell state: the kind in the high bits, team and hits below
+fn cell_code(cell: Cell) -> u8 {
+    match cell {
+        Cell::Neutral => 0,
+        Cell::Wall => 1,
+        Cell::Void => 2,
+        Cell::Team(team) => 0x10 | team.0,
+        Cell::Armored { owner, hits } => {
+            let owner = owner.map_or(0, |team| team.0 + 1);
+            0x80 | (owner << 3) | hits
+        }
+    }
+}
+
+fn cell_from_code(code: u8) -> Result<Cell, ReplayError> {
+    let team = |id: u8| {
+        let team = TeamId(id);
+        TeamId::ALL
+            .contains(&team)
+            .then_some(team)
+            .ok_or(ReplayError::Corrupt("unknown team"))
+    };
+    Ok(match code {
+        0 => Cell::Neutral,
+        1 => Cell::Wall,
+        2 => Cell::Void,
+        0x10..=0x1f => Cell::Team(team(code & 0x0f)?),
+        0x80..=0xff => Cell::Armored {
+            owner: match (code >> 3) & 0x0f {
+                0 => None,
+                owner => Some(team(owner - 1)?),
+            },
+            hits: code & 0x07,
+        },
+        _ => return Err(ReplayError::Corrupt("unknown cell")),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::region_sim::TICK_SECONDS;
+
+    fn settings() -> MatchSettings {
+        MatchSettings {
+            board_size: 15,
+            balls_per_team: 2,
+            seed: Some(7),
+            steering: true,
+            barriers: true,
+            ..MatchSettings::default()
+        }
+    }
+
+    // Plays `ticks` ticks the way the game does, with a few player inputs
+    fn record(settings: &MatchSettings, ticks: u32) -> (RegionWorld, Recorder) {
+        let mut world = RegionWorld::new(settings);
+        let mut recorder = Recorder::new(settings, TICK_SECONDS);
+        while world.tick() < ticks {
+            if world.tick() % 100 == 50 {
+                let direction = Vec2::from_angle(world.tick() as f32);
+                recorder.apply(
+                    &mut world,
+                    ReplayInput::Steer {
+                        team: TeamId::RED,
+                        input: SteeringInput {
+                            direction,
+                            boost: false,
+                        },
+                    },
+                );
+            }
+            if world.tick() == 120 {
+                recorder.apply(
+                    &mut world,
+                    ReplayInput::Barrier {
+                        team: TeamId::BLUE,
+                        center: Vec2::ZERO,
+                        vertical: true,
+                    },
+                );
+            }
+            world.step(TICK_SECONDS);
+            recorder.record_tick(&world);
+        }
+        (world, recorder)
+    }
+
+    #[test]
+    fn round_trip_plays_back_to_the_same_board() {
+        let (world, recorder) = record(&settings(), 1000);
+        assert!(recorder
+            .replay()
+            .inputs
+            .iter()
+            .any(|(_, input)| matches!(input, ReplayInput::Barrier { .. })));
+        let replay = Replay::from_bytes(&recorder.replay().to_bytes()).unwrap();
+        assert_eq!(&replay, recorder.replay());
+
+        let mut playback = Playback::new(replay);
+        let mut played = playback.start();
+        while played.tick() < world.tick() {
+            playback.step(&mut played).unwrap();
+        }
+        assert_eq!(played.cells(), world.cells());
+        assert_eq!(played.balls(), world.balls());
+
+        // and again by jumping back from the end
+        playback.seek(&mut played, 300).unwrap();
+        playback.seek(&mut played, world.tick()).unwrap();
+        assert_eq!(played.cells(), world.cells());
+        assert_eq!(played.balls(), world.balls());
+    }
+
+    #[test]
+    fn rejects_more_balls_than_fit() {
+        let settings = MatchSettings {
+            board_size: 8,
+            balls_per_team: 9,
+            ..MatchSettings::default()
+        };
+        let bytes = Recorder::new(&settings, TICK_SECONDS).replay().to_bytes();
+        assert_eq!(
+            Replay::from_bytes(&bytes),
+            Err(ReplayError::Corrupt("too many balls"))
+        );
+    }
+
+    #[test]
+    fn rejects_empty_maps() {
+        let settings = MatchSettings {
+            map: Some(RegionMap {
+                name: "empty".to_string(),
+                author: None,
+                description: None,
+                columns: 0,
+                rows: 4,
+                cells: Vec::new(),
+                spawns: Vec::new(),
+            }),
+            ..MatchSettings::default()
+        };
+        let bytes = Recorder::new(&settings, TICK_SECONDS).replay().to_bytes();
+        assert_eq!(
+            Replay::from_bytes(&bytes),
+            Err(ReplayError::Corrupt("empty map"))
+        );
+    }
+
+    #[test]
+    fn rejects_other_formats_and_simulation_versions() {
+        let bytes = Recorder::new(&settings(), TICK_SECONDS).replay().to_bytes();
+        let mut old_format = bytes.clone();
+        old_format[MAGIC.len()] = 1;
+        assert_eq!(
+            Replay::from_bytes(&old_format),
+            Err(ReplayError::Format { found: 1 })
+        );
+
+        let header = |version: u32| {
+            let mut writer = Writer::default();
+            writer.bytes.extend_from_slice(MAGIC);
+            writer.u8(FORMAT);
+            writer.varint(version as u64);
+            writer.bytes
+        };
+        let body = &bytes[header(SIM_VERSION).len()..];
+        let newer = [header(SIM_VERSION + 1).as_slice(), body].concat();
+        assert_eq!(
+            Replay::from_bytes(&newer),
+            Err(ReplayError::Version {
+                found: SIM_VERSION + 1
+            })
+        );
+    }
+}
@@ -0,0 +1,280 @@
+// Matches saved to be resumed later. The random number generator of a world can
+// not be written out, so a save holds the replay of the match so far and
+// resuming plays it again. The board, the balls and the clock at the time of
+// saving are stored too, and resuming fails unless the replay arrives at them.
+//
+//     "RBSV", format, simulation version, replay, tick, cells, balls, speeds
+//
+// Saves in another file format or of another `SIM_VERSION` are rejected before
+// anything is played back.
+use std::fmt;
+
+use super::{
+    replay::{Reader, ReplayError, Writer},
+    Ball, Cell, Playback, Recorder, RegionWorld, Replay, TeamId, SIM_VERSION,
+};
+
+const MAGIC: &[u8; 4] = b"RBSV";
+const FORMAT: u8 = 2;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SaveError {
+    NotASave,
+    /// Written in a file format this version can not read.
+    Format {
+        found: u8,
+    },
+    /// The file ends early or holds values no save can contain.
+    Corrupt(&'static str),
+    /// Saved with another `SIM_VERSION`, whose matches play out differently.
+    Outdated {
+        found: u32,
+    },
+    /// Playing the match again did not get back to the saved state.
+    Mismatch {
+        tick: u32,
+    },
+}
+
+impl fmt::Display for SaveError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SaveError::NotASave => write!(f, "not a Region Battle save"),
+            SaveError::Format { found } => {
+                write!(f, "save format {found} is not supported, expected {FORMAT}")
+            }
+            SaveError::Corrupt(what) => write!(f, "save is damaged: {what}"),
+            SaveError::Outdated { found } => write!(
+                f,
+                "match was saved with simulation version {found} and can not be resumed in version {SIM_VERSION}"
+            ),
+            SaveError::Mismatch { tick } => {
+                write!(f, "saved match no longer plays back the same at tick {tick}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for SaveError {}
+
+impl From<ReplayError> for SaveError {
+    fn from(error: ReplayError) -> Self {
+        match error {
+            ReplayError::NotAReplay => SaveError::NotASave,
+            ReplayError::Format { found } => SaveError::Format { found },
+            ReplayError::Version { found } => SaveError::Outdated { found },
+            ReplayError::Corrupt(what) => SaveError::Corrupt(what),
+            ReplayError::Desync { tick } => SaveError::Mismatch { tick },
+        }
+    }
+}
+
+/// A match stopped part way through.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SavedMatch {
+    replay: Replay,
+    tick: u32,
+    cells: Vec<Cell>,
+    balls: Vec<Ball>,
+    speeds: Vec<f32>,
+}
+
+impl SavedMatch {
+    /// Saves `world`, whose match `recorder` recorded.
+    pub fn new(recorder: &Recorder, world: &RegionWorld) -> Self {
+        Self {
+            replay: recorder.replay().clone(),
+            tick: world.tick(),
+            cells: world.cells().to_vec(),
+            balls: world.balls().to_vec(),
+            speeds: TeamId::ALL.map(|team| world.speed(team)).to_vec(),
+        }
+    }
+
+    /// Plays the match again up to the tick it was saved on, and returns the
+    /// world with the recorder to carry on with. `on_tick` sees the world after
+    /// every tick played again, so views can catch up on the match so far.
+    pub fn resume(
+        self,
+        mut on_tick: impl FnMut(&RegionWorld),
+    ) -> Result<(RegionWorld, Recorder), SaveError> {
+        let mut playback = Playback::new(self.replay);
+        let mut world = playback.start();
+        let end = self.tick.min(playback.replay().ticks());
+        let mut played = Ok(());
+        while played.is_ok() && world.tick() < end {
+            played = playback.step(&mut world);
+            on_tick(&world);
+            world.clear_changes();
+        }
+        world.touch_all_cells();
+        let speeds: Vec<f32> = TeamId::ALL.map(|team| world.speed(team)).to_vec();
+        if played.is_err()
+            || world.tick() != self.tick
+            || world.cells() != self.cells
+            || world.balls() != self.balls
+            || speeds != self.speeds
+        {
+            return Err(played
+                .err()
+                .map_or(SaveError::Mismatch { tick: self.tick }, SaveError::from));
+        }
+        Ok((world, Recorder::resume(playback.replay().clone())))
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut writer = Writer::default();
+        writer.bytes.extend_from_slice(MAGIC);
+        writer.u8(FORMAT);
+        writer.varint(SIM_VERSION as u64);
+        self.replay.write(&mut writer);
+        writer.varint(self.tick as u64);
+        writer.cells(&self.cells);
+        writer.varint(self.balls.len() as u64);
+        for ball in &self.balls {
+            writer.u8(ball.team.0);
+            writer.vec2(ball.position);
+            writer.vec2(ball.velocity);
+            writer.f32(ball.radius);
+        }
+        writer.varint(self.speeds.len() as u64);
+        for speed in &self.speeds {
+            writer.f32(*speed);
+        }
+        writer.bytes
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<SavedMatch, SaveError> {
+        if !bytes.starts_with(MAGIC) {
+            return Err(SaveError::NotASave);
+        }
+        let mut reader = Reader::new(bytes, MAGIC.len());
+        let format = reader.u8()?;
+        if format != FORMAT {
+            return Err(SaveError::Format { found: format });
+        }
+        let version = reader.u32()?;
+        if version != SIM_VERSION {
+            return Err(SaveError::Outdated { found: version });
+        }
+        let replay = Replay::read(&mut reader)?;
+        let tick = reader.u32()?;
+        if tick != replay.ticks() {
+            return Err(SaveError::Corrupt("saved tick does not match the replay"));
+        }
+        let cells = reader.cells()?;
+        let mut balls = Vec::new();
+        for _ in 0..reader.len()? {
+            balls.push(Ball {
+                team: reader.team()?,
+                position: reader.vec2()?,
+                velocity: reader.vec2()?,
+                radius: reader.f32()?,
+            });
+        }
+        let mut speeds = Vec::new();
+        for _ in 0..reader.len()? {
+            speeds.push(reader.f32()?);
+        }
+        reader.finish()?;
+        Ok(SavedMatch {
+            replay,
+            tick,
+            cells,
+            balls,
+            speeds,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::region_sim::{MatchSettings, TICK_SECONDS};
+
+    #[test]
+    fn resume_plays_back_to_the_saved_board() {
+        let settings = MatchSettings {
+            board_size: 15,
+            balls_per_team: 3,
+            seed: Some(11),
+            ..MatchSettings::default()
+        };
+        let mut world = RegionWorld::new(&settings);
+        let mut recorder = Recorder::new(&settings, TICK_SECONDS);
+        for _ in 0..700 {
+            world.step(TICK_SECONDS);
+            recorder.record_tick(&world);
+        }
+
+        let saved = SavedMatch::new(&recorder, &world);
+        let loaded = SavedMatch::from_bytes(&saved.to_bytes()).unwrap();
+        assert_eq!(loaded, saved);
+        let mut ticks = Vec::new();
+        let (mut resumed, mut resumed_recorder) =
+            loaded.resume(|world| ticks.push(world.tick())).unwrap();
+        assert_eq!(ticks, (1..=700).collect::<Vec<_>>());
+        assert_eq!(resumed.tick(), world.tick());
+        assert_eq!(resumed.cells(), world.cells());
+        assert_eq!(resumed.balls(), world.balls());
+
+        // both carry on the same way
+        for _ in 0..300 {
+            world.step(TICK_SECONDS);
+            recorder.record_tick(&world);
+            resumed.step(TICK_SECONDS);
+            resumed_recorder.record_tick(&resumed);
+        }
+        assert_eq!(resumed.cells(), world.cells());
+        assert_eq!(resumed.balls(), world.balls());
+        assert_eq!(resumed_recorder.replay(), recorder.replay());
+    }
+
+    #[test]
+    fn rejects_damaged_and_foreign_files() {
+        let settings = MatchSettings {
+            board_size: 8,
+            seed: Some(5),
+            ..MatchSettings::default()
+        };
+        let mut world = RegionWorld::new(&settings);
+        let mut recorder = Recorder::new(&settings, TICK_SECONDS);
+        for _ in 0..100 {
+            world.step(TICK_SECONDS);
+            recorder.record_tick(&world);
+        }
+        let bytes = SavedMatch::new(&recorder, &world).to_bytes();
+
+        for end in MAGIC.len()..bytes.len() {
+            let truncated = SavedMatch::from_bytes(&bytes[..end]);
+            assert!(
+                matches!(truncated, Err(SaveError::Corrupt(_))),
+                "{end}: {truncated:?}"
+            );
+        }
+        assert_eq!(SavedMatch::from_bytes(b""), Err(SaveError::NotASave));
+        let mut bad_magic = bytes.clone();
+        bad_magic[0] = b'X';
+        assert_eq!(SavedMatch::from_bytes(&bad_magic), Err(SaveError::NotASave));
+        let replay = recorder.replay().to_bytes();
+        assert_eq!(SavedMatch::from_bytes(&replay), Err(SaveError::NotASave));
+
+        let mut old_format = bytes.clone();
+        old_format[MAGIC.len()] = 1;
+        assert_eq!(
+            SavedMatch::from_bytes(&old_format),
+            Err(SaveError::Format { found: 1 })
+        );
+        // the header is checked before the rest of the file is even read
+        let mut writer = Writer::default();
+        writer.bytes.extend_from_slice(MAGIC);
+        writer.u8(FORMAT);
+        writer.varint(SIM_VERSION as u64 + 1);
+        assert_eq!(
+            SavedMatch::from_bytes(&writer.bytes),
+            Err(SaveError::Outdated {
+                found: SIM_VERSION + 1
+            })
+        );
+    }
+}
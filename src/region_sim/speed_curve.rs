@@ -0,0 +1,53 @@
+// Balls that speed up as the match goes on, so long matches do not drag. Every
+// team has a pace multiplier that grows with its captures and with time, up to
+// a cap, and jumps past the cap once sudden death starts.
+
+/// Extra pace for every ball once sudden death starts, on top of the cap.
+pub const SUDDEN_DEATH_SPEED: f32 = 1.5;
+
+/// How ball speed changes during a match. The default keeps it constant.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SpeedCurve {
+    /// Fraction a team's balls speed up for each brick the team captures.
+    pub per_capture: f32,
+    /// Fraction every ball speeds up per second.
+    pub per_second: f32,
+    /// Highest pace outside of sudden death, as a multiple of the launch speed.
+    pub max_speed: f32,
+    /// Ticks before the tick limit at which sudden death starts. Needs a tick
+    /// limit.
+    pub sudden_death: Option<u32>,
+}
+
+impl Default for SpeedCurve {
+    fn default() -> Self {
+        Self {
+            per_capture: 0.,
+            per_second: 0.,
+            max_speed: 1.,
+            sudden_death: None,
+        }
+    }
+}
+
+impl SpeedCurve {
+    /// `pace` after `dt` seconds and `captures` captures.
+    pub(super) fn accelerate(&self, pace: f32, captures: usize, dt: f32) -> f32 {
+        let growth =
+            (1. + self.per_capture).powi(captures as i32) * (1. + self.per_second).powf(dt);
+        self.speed_up(pace, growth)
+    }
+
+    /// `pace` multiplied by `factor`, up to the cap.
+    pub(super) fn speed_up(&self, pace: f32, factor: f32) -> f32 {
+        (pace * factor).min(self.max_speed.max(1.))
+    }
+
+    /// Whether sudden death is on at `tick` of a match ending at `tick_limit`.
+    pub(super) fn is_sudden_death(&self, tick: u32, tick_limit: Option<u32>) -> bool {
+        match (self.sudden_death, tick_limit) {
+            (Some(ticks), Some(limit)) => tick + ticks >= limit,
+            _ => false,
+        }
+    }
+}
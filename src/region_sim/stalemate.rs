@@ -0,0 +1,168 @@
+// Detects matches that stopped going anywhere. Bounces off axis-aligned bricks
+// easily settle into loops where the same cells change hands forever, which
+// shows up either as the exact same balls and board coming back (a cycle) or as
+// a long stretch where neither team gains ground (a stall).
+use std::{
+    collections::HashMap,
+    fmt,
+    hash::{DefaultHasher, Hash, Hasher},
+};
+
+use super::{Ball, Cell};
+
+// the full state is only hashed every other tick, a cycle is caught within a few
+// more periods anyway
+const SAMPLE_TICKS: u32 = 2;
+// forget old states rather than growing forever in very long matches
+const MAX_SAMPLES: usize = 8192;
+// a minute of the score staying within `STALL_MARGIN` of where it was
+const STALL_TICKS: u32 = 64 * 60;
+// fraction of the capturable cells a team has to gain for the match to count
+// as moving
+const STALL_MARGIN: f32 = 0.01;
+// ball state is rounded to whole pixels. A loop rarely lasts a whole number of
+// ticks, so the samples only come back close to, not exactly on, earlier ones
+const QUANTUM: f32 = 1.;
+
+/// What the simulation does once it detects a stalemate.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum StalemateResponse {
+    /// Turns every ball by a small random angle.
+    #[default]
+    Jitter,
+    /// Makes every ball faster, up to the speed curve's `max_speed`.
+    SpeedUp,
+    /// Ends the match as a draw.
+    Draw,
+}
+
+impl StalemateResponse {
+    pub const ALL: [StalemateResponse; 3] = [
+        StalemateResponse::Jitter,
+        StalemateResponse::SpeedUp,
+        StalemateResponse::Draw,
+    ];
+
+    /// Name used on the command line.
+    pub fn name(self) -> &'static str {
+        match self {
+            StalemateResponse::Jitter => "jitter",
+            StalemateResponse::SpeedUp => "speed-up",
+            StalemateResponse::Draw => "draw",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<StalemateResponse> {
+        StalemateResponse::ALL
+            .into_iter()
+            .find(|response| response.name() == name)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StalemateKind {
+    /// Balls and board came back to a state seen `period` ticks earlier.
+    Cycle { period: u32 },
+    /// No team gained ground for `ticks` ticks.
+    Stall { ticks: u32 },
+}
+
+/// A detected stalemate and what was done about it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Stalemate {
+    pub tick: u32,
+    pub kind: StalemateKind,
+    pub response: StalemateResponse,
+}
+
+impl fmt::Display for Stalemate {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "stalemate at tick {}, ", self.tick)?;
+        match self.kind {
+            StalemateKind::Cycle { period } => write!(f, "cycle of {period} ticks")?,
+            StalemateKind::Stall { ticks } => write!(f, "no progress for {ticks} ticks")?,
+        }
+        write!(f, ", {}", self.response.name())
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub(super) struct Detector {
+    // hash of balls and board to the tick it was sampled at
+    seen: HashMap<u64, u32>,
+    window_start: u32,
+    baseline: Vec<usize>,
+}
+
+impl Detector {
+    /// Starts watching again from `tick`, after a response changed the match.
+    pub fn reset(&mut self, tick: u32, scores: &[usize]) {
+        self.seen.clear();
+        self.reset_window(tick, scores);
+    }
+
+    pub fn check(
+        &mut self,
+        tick: u32,
+        balls: &[Ball],
+        board_hash: u64,
+        scores: &[usize],
+        capturable: usize,
+    ) -> Option<StalemateKind> {
+        if tick.is_multiple_of(SAMPLE_TICKS) {
+            if self.seen.len() >= MAX_SAMPLES {
+                self.seen.clear();
+            }
+            let state = state_hash(balls, board_hash);
+            if let Some(previous) = self.seen.insert(state, tick) {
+                return Some(StalemateKind::Cycle {
+                    period: tick - previous,
+                });
+            }
+        }
+
+        let margin = (capturable as f32 * STALL_MARGIN).ceil() as usize;
+        let moved = scores
+            .iter()
+            .zip(&self.baseline)
+            .any(|(score, baseline)| score.abs_diff(*baseline) > margin);
+        if moved || self.baseline.len() != scores.len() {
+            self.reset_window(tick, scores);
+            return None;
+        }
+        let ticks = tick - self.window_start;
+        (ticks >= STALL_TICKS).then_some(StalemateKind::Stall { ticks })
+    }
+
+    fn reset_window(&mut self, tick: u32, scores: &[usize]) {
+        self.window_start = tick;
+        self.baseline.clear();
+        self.baseline.extend_from_slice(scores);
+    }
+}
+
+/// Hash of one cell in one state. The board hash is the XOR of these over all
+/// cells, so a capture only has to swap one term.
+pub(super) fn cell_hash(cell: usize, state: Cell) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    (cell, state).hash(&mut hasher);
+    hasher.finish()
+}
+
+fn state_hash(balls: &[Ball], board_hash: u64) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    board_hash.hash(&mut hasher);
+    for ball in balls {
+        let quantize = |value: f32| (value * QUANTUM).round() as i64;
+        (
+            ball.team,
+            quantize(ball.position.x),
+            quantize(ball.position.y),
+            quantize(ball.velocity.x),
+            quantize(ball.velocity.y),
+            quantize(ball.radius),
+        )
+            .hash(&mut hasher);
+    }
+    hasher.finish()
+}
@@ -0,0 +1,68 @@
+// Player control for local two-player matches. Players never move a ball
+// directly: they pick a direction and every ball of their team turns towards it
+// at a limited rate, still bouncing and capturing like an automatic ball.
+use bevy::math::Vec2;
+
+/// Radians per second a steered ball can turn.
+pub const TURN_RATE: f32 = std::f32::consts::PI;
+/// Speed multiplier while boosting.
+pub const BOOST_SPEED: f32 = 1.75;
+// a full meter lasts two seconds and refills in eight
+const BOOST_DRAIN: f32 = 0.5;
+const BOOST_RECHARGE: f32 = 0.125;
+// charge needed to start a burst, so holding the key on an empty meter does not
+// stutter between boosting and recharging
+const BOOST_START: f32 = 0.2;
+
+/// What a team's player is currently asking for.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct SteeringInput {
+    /// Direction to turn towards, zero to keep going straight.
+    pub direction: Vec2,
+    pub boost: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(super) struct Steering {
+    pub input: SteeringInput,
+    /// Boost left, from `0` to `1`. `None` without a boost meter.
+    pub meter: Option<f32>,
+    /// How fast the team's balls move this tick, `BOOST_SPEED` while boosting.
+    pub pace: f32,
+}
+
+impl Steering {
+    pub fn new(boost: bool) -> Self {
+        Self {
+            input: SteeringInput::default(),
+            meter: boost.then_some(1.),
+            pace: 1.,
+        }
+    }
+
+    /// Drains or recharges the meter over `dt` seconds.
+    pub fn update(&mut self, dt: f32) {
+        let boosting = self.pace > 1.;
+        self.pace = 1.;
+        let Some(meter) = &mut self.meter else {
+            return;
+        };
+        let threshold = if boosting { 0. } else { BOOST_START };
+        if self.input.boost && *meter > threshold {
+            *meter = (*meter - BOOST_DRAIN * dt).max(0.);
+            self.pace = BOOST_SPEED;
+        } else {
+            *meter = (*meter + BOOST_RECHARGE * dt).min(1.);
+        }
+    }
+}
+
+/// `velocity` turned towards `direction` by at most `TURN_RATE * dt`.
+pub fn steer(velocity: Vec2, direction: Vec2, dt: f32) -> Vec2 {
+    if direction == Vec2::ZERO || velocity == Vec2::ZERO {
+        return velocity;
+    }
+    let max_turn = TURN_RATE * dt;
+    let angle = velocity.angle_to(direction).clamp(-max_turn, max_turn);
+    Vec2::from_angle(angle).rotate(velocity)
+}
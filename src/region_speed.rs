@@ -0,0 +1,120 @@
+// Simulation speed of Region Battle, to watch a long match quickly or a collision
+// slowly. Only the rate of `Time<Fixed>` changes: every tick still simulates
+// `TICK_SECONDS`, so a match plays out the same at any speed and its replay stays
+// in sync. While the game is paused, single ticks can be stepped.
+use std::time::Duration;
+
+use bevy::{app::FixedMain, prelude::*};
+
+use crate::{
+    common::{FIRASANS_FONT, GAME_DATA_TEXT_COLOR},
+    pause::PauseState,
+    region_sim::TICK_SECONDS,
+    utils::despawn_with_component,
+    GameState,
+};
+
+const SIM_SPEEDS: [f32; 6] = [0.1, 0.5, 1., 2., 4., 16.];
+const NORMAL_SPEED: usize = 2;
+
+pub struct RegionSpeedPlugin;
+
+impl Plugin for RegionSpeedPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(OnEnter(GameState::RegionGame), setup_speed_display)
+            .add_systems(
+                OnExit(GameState::RegionGame),
+                (reset_speed, despawn_with_component::<SpeedDisplay>),
+            )
+            .add_systems(
+                Update,
+                (
+                    (speed_keys, update_speed_display).chain(),
+                    step_tick.run_if(in_state(PauseState::Paused)),
+                )
+                    .run_if(in_state(GameState::RegionGame)),
+            );
+    }
+}
+
+// Index into `SIM_SPEEDS`
+#[derive(Resource)]
+struct SimSpeed(usize);
+
+#[derive(Component)]
+struct SpeedDisplay;
+
+fn setup_speed_display(mut commands: Commands, asset_server: Res<AssetServer>) {
+    commands.insert_resource(SimSpeed(NORMAL_SPEED));
+    commands.spawn((
+        Text::default(),
+        TextFont {
+            font: asset_server.load(FIRASANS_FONT),
+            font_size: 20.0,
+            ..default()
+        },
+        TextColor(GAME_DATA_TEXT_COLOR),
+        Node {
+            position_type: PositionType::Absolute,
+            top: Val::Px(10.0),
+            right: Val::Px(10.),
+            ..default()
+        },
+        SpeedDisplay,
+    ));
+}
+
+fn reset_speed(mut commands: Commands, mut time: ResMut<Time<Fixed>>) {
+    commands.remove_resource::<SimSpeed>();
+    time.set_timestep_seconds(TICK_SECONDS as f64);
+}
+
+// [ and ] slow the match down and speed it up
+fn speed_keys(
+    keys: Res<ButtonInput<KeyCode>>,
+    mut speed: ResMut<SimSpeed>,
+    mut time: ResMut<Time<Fixed>>,
+) {
+    if keys.just_pressed(KeyCode::BracketLeft) {
+        speed.0 = speed.0.saturating_sub(1);
+    }
+    if keys.just_pressed(KeyCode::BracketRight) {
+        speed.0 = (speed.0 + 1).min(SIM_SPEEDS.len() - 1);
+    }
+    if speed.is_changed() {
+        time.set_timestep(Duration::from_secs_f64(
+            TICK_SECONDS as f64 / SIM_SPEEDS[speed.0] as f64,
+        ));
+    }
+}
+
+fn update_speed_display(
+    speed: Res<SimSpeed>,
+    pause_state: Option<Res<State<PauseState>>>,
+    mut displays: Query<&mut Text, With<SpeedDisplay>>,
+) {
+    let paused = pause_state.is_some_and(|state| *state.get() == PauseState::Paused);
+    for mut text in &mut displays {
+        **text = if paused {
+            format!("SIM x{} (. steps a tick)", SIM_SPEEDS[speed.0])
+        } else {
+            format!("SIM x{}", SIM_SPEEDS[speed.0])
+        };
+    }
+}
+
+// . plays a single tick while the game is paused, the way the fixed main loop
+// would
+fn step_tick(world: &mut World) {
+    if !world
+        .resource::<ButtonInput<KeyCode>>()
+        .just_pressed(KeyCode::Period)
+    {
+        return;
+    }
+    let timestep = world.resource::<Time<Fixed>>().timestep();
+    world.resource_mut::<Time<Fixed>>().advance_by(timestep);
+    *world.resource_mut::<Time>() = world.resource::<Time<Fixed>>().as_generic();
+    world.run_schedule(FixedMain);
+    *world.resource_mut::<Time>() = world.resource::<Time<Virtual>>().as_generic();
+}
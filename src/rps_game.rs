@@ -2,6 +2,7 @@ use bevy::prelude::*;
 
 use crate::{
     common::{FIRASANS_FONT, NORMAL_BUTTON, TEXT_COLOR},
+    pause::PauseMenuExt,
     utils::{common_button_system, despawn_with_component},
     GameState,
 };
@@ -10,7 +11,8 @@ pub struct RpsGamePlugin;
 
 impl Plugin for RpsGamePlugin {
     fn build(&self, app: &mut App) {
-        app.add_systems(OnEnter(GameState::RpsGame), setup_basedata)
+        app.add_pause_menu(GameState::RpsGame, Some(GameState::Options))
+            .add_systems(OnEnter(GameState::RpsGame), setup_basedata)
             .add_systems(
                 OnExit(GameState::RpsGame),
                 despawn_with_component::<ReturnButton>,
@@ -36,7 +38,8 @@ fn setup_basedata(mut commands: Commands, asset_server: Res<AssetServer>) {
                 left: Val::Px(10.),
                 ..default()
             },
-            TextColor(NORMAL_BUTTON),
+            BackgroundColor(NORMAL_BUTTON),
+            Button,
             ReturnButton,
         ))
         .with_children(|parent| {
@@ -71,7 +74,7 @@ fn setup_basedata(mut commands: Commands, asset_server: Res<AssetServer>) {
 }
 
 fn menu_action(
-    interaction_query: Query<&Interaction, (Changed<Interaction>, With<Button>)>,
+    interaction_query: Query<&Interaction, (Changed<Interaction>, With<ReturnButton>)>,
     mut game_state: ResMut<NextState<GameState>>,
 ) {
     for interaction in &interaction_query {
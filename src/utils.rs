@@ -1,4 +1,7 @@
-use crate::common::*;
+use crate::{
+    audio::{PlaySfx, Sfx},
+    common::*,
+};
 
 use bevy::prelude::*;
 
@@ -12,8 +15,18 @@ pub fn common_button_system(
         (&Interaction, &mut BackgroundColor, Option<&SelectedOption>),
         (Changed<Interaction>, With<Button>),
     >,
+    mut sfx: EventWriter<PlaySfx>,
 ) {
     for (interaction, mut color, selected) in &mut interaction_query {
+        match interaction {
+            Interaction::Pressed => {
+                sfx.send(PlaySfx(Sfx::Press));
+            }
+            Interaction::Hovered => {
+                sfx.send(PlaySfx(Sfx::Hover));
+            }
+            Interaction::None => {}
+        }
         *color = match (*interaction, selected) {
             (Interaction::Pressed, _) | (Interaction::None, Some(_)) => PRESSED_BUTTON.into(),
             (Interaction::Hovered, Some(_)) => HOVERED_PRESSED_BUTTON.into(),